@@ -14,6 +14,23 @@ pub(crate) struct RunAttrs {
     pub generics: syn::Generics,
     /// Which handler function to run for this command
     pub run: Option<syn::Path>,
+    /// Handler function that runs after this command's subcommands
+    /// complete successfully.
+    pub after: Option<syn::Path>,
+    /// Handler function that runs after this command and its subcommands
+    /// complete, whether they succeeded or failed.
+    pub finally: Option<syn::Path>,
+    /// Deadline for each handler of this command, e.g. "30s".
+    pub timeout: Option<syn::LitStr>,
+
+    pub attrs: Vec<syn::Attribute>,
+}
+
+impl RunAttrs {
+    /// The `#[command(rename_all = "...")]` case of the subcommands.
+    pub fn rename_all(&self) -> Option<String> {
+        command_value(&self.attrs, "rename_all")
+    }
 }
 
 // Attributes for struct-field level #[cling(...)]
@@ -32,6 +49,9 @@ pub(crate) struct StructFieldAttrs {
     #[darling(default)]
     pub skip: bool,
 
+    /// Function that returns completion candidates for this field's value.
+    pub complete: Option<syn::Path>,
+
     pub attrs: Vec<syn::Attribute>,
 }
 
@@ -56,6 +76,24 @@ pub(crate) struct EnumVariantAttrs {
     pub fields: darling::ast::Fields<VariantFieldAttrs>,
     /// Which handler function to run for this command
     pub run: Option<syn::Path>,
+    /// Deadline for the handler of this variant, e.g. "30s".
+    pub timeout: Option<syn::LitStr>,
+
+    pub attrs: Vec<syn::Attribute>,
+}
+
+impl EnumVariantAttrs {
+    /// The name of the subcommand on the command line, as clap derives it
+    /// with the `rename_all` case of the enum.
+    pub fn subcommand_name(&self, rename_all: Option<&str>) -> String {
+        command_value(&self.attrs, "name").unwrap_or_else(|| {
+            rename(&self.ident.to_string(), rename_all.unwrap_or("kebab-case"))
+        })
+    }
+
+    pub fn is_flatten(&self) -> bool {
+        has_command_flag(&self.attrs, "flatten")
+    }
 }
 
 // Attributes for derive Collect
@@ -66,11 +104,15 @@ pub(crate) struct CollectAttrs {
 }
 
 fn has_subcommand(attrs: &[syn::Attribute]) -> bool {
+    has_command_flag(attrs, "subcommand")
+}
+
+fn has_command_flag(attrs: &[syn::Attribute], flag: &str) -> bool {
     for attr in attrs {
         if attr.path().is_ident("command") || attr.path().is_ident("clap") {
             if let Ok(meta_list) = parse_attribute_to_meta_list(attr) {
                 if let Ok(arg) = meta_list.parse_args::<syn::Ident>() {
-                    if arg == "subcommand" {
+                    if arg == flag {
                         return true;
                     }
                 }
@@ -79,3 +121,133 @@ fn has_subcommand(attrs: &[syn::Attribute]) -> bool {
     }
     false
 }
+
+/// Renames an identifier to a `rename_all` case the way clap does.
+fn rename(ident: &str, case: &str) -> String {
+    use heck::{
+        ToKebabCase,
+        ToLowerCamelCase,
+        ToShoutySnakeCase,
+        ToSnakeCase,
+        ToUpperCamelCase,
+    };
+    match case.to_ascii_lowercase().replace(['-', '_'], "").as_str() {
+        | "camel" | "camelcase" => ident.to_lower_camel_case(),
+        | "pascal" | "pascalcase" => ident.to_upper_camel_case(),
+        | "screamingsnake" | "screamingsnakecase" => {
+            ident.to_shouty_snake_case()
+        }
+        | "snake" | "snakecase" => ident.to_snake_case(),
+        | "lower" | "lowercase" => ident.to_snake_case().replace('_', ""),
+        | "upper" | "uppercase" => {
+            ident.to_shouty_snake_case().replace('_', "")
+        }
+        | "verbatim" | "verbatimcase" => ident.to_owned(),
+        // clap rejects other cases, so this is only reached for kebab-case.
+        | _ => ident.to_kebab_case(),
+    }
+}
+
+/// Finds `#[command(<key> = "...")]` in the attributes.
+fn command_value(attrs: &[syn::Attribute], key: &str) -> Option<String> {
+    let mut name = None;
+    for attr in attrs {
+        if !(attr.path().is_ident("command") || attr.path().is_ident("clap")) {
+            continue;
+        }
+        let Ok(metas) = attr.parse_args_with(
+            syn::punctuated::Punctuated::<syn::Meta, syn::Token![,]>::parse_terminated,
+        ) else {
+            continue;
+        };
+        for meta in metas {
+            if let syn::Meta::NameValue(meta) = meta {
+                if meta.path.is_ident(key) {
+                    if let syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(value),
+                        ..
+                    }) = meta.value
+                    {
+                        name = Some(value.value());
+                    }
+                }
+            }
+        }
+    }
+    name
+}
+
+/// Parses a duration like "30s", "500ms" or "1m30s" into milliseconds.
+pub(crate) fn parse_timeout(lit: &syn::LitStr) -> darling::Result<u64> {
+    let invalid = || {
+        darling::Error::custom(format!(
+            "Invalid timeout `{}`. Expected a duration like \"30s\", \
+             \"500ms\" or \"1m30s\" (units: ms, s, m, h)",
+            lit.value()
+        ))
+        .with_span(lit)
+    };
+    let value = lit.value();
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    let mut millis: u64 = 0;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or_else(invalid)?;
+        let amount: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        rest = &rest[digits..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let factor = match &rest[..unit_len] {
+            | "ms" => 1,
+            | "s" => 1_000,
+            | "m" => 60_000,
+            | "h" => 3_600_000,
+            | _ => return Err(invalid()),
+        };
+        rest = &rest[unit_len..];
+        millis = amount
+            .checked_mul(factor)
+            .and_then(|amount| millis.checked_add(amount))
+            .ok_or_else(invalid)?;
+    }
+    Ok(millis)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: &str) -> Option<u64> {
+        let lit = syn::LitStr::new(value, proc_macro2::Span::call_site());
+        parse_timeout(&lit).ok()
+    }
+
+    #[test]
+    fn test_parse_timeout() {
+        assert_eq!(Some(30_000), parse("30s"));
+        assert_eq!(Some(500), parse("500ms"));
+        assert_eq!(Some(90_000), parse("1m30s"));
+        assert_eq!(Some(7_200_000), parse("2h"));
+        assert_eq!(None, parse(""));
+        assert_eq!(None, parse("30"));
+        assert_eq!(None, parse("s"));
+        assert_eq!(None, parse("30 seconds"));
+    }
+
+    #[test]
+    fn test_rename() {
+        assert_eq!("show-all", rename("ShowAll", "kebab-case"));
+        assert_eq!("show_all", rename("ShowAll", "snake_case"));
+        assert_eq!("SHOW_ALL", rename("ShowAll", "SCREAMING_SNAKE_CASE"));
+        assert_eq!("showAll", rename("ShowAll", "camelCase"));
+        assert_eq!("ShowAll", rename("ShowAll", "PascalCase"));
+        assert_eq!("showall", rename("ShowAll", "lower"));
+        assert_eq!("SHOWALL", rename("ShowAll", "UPPER"));
+        assert_eq!("ShowAll", rename("ShowAll", "verbatim"));
+    }
+}
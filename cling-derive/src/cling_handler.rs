@@ -37,11 +37,11 @@ pub fn expand_handler(input: &ItemFn) -> TokenStream {
     quote::quote! {
          const _: () = {
             const fn assert_cling_into_effect<T: ::cling::_private::IntoEffect<A>, A>() {}
-            const fn assert_cling_param_type<'a, T: ::cling::_private::HandlerParam<'a>>() {}
+            const fn assert_cling_param_type<'a, T: ::cling::_private::FromCollected<'a>>() {}
 
             // Validate that output type implements IntoEffect
             #output_check
-            // Validate that function inputs implement HandlerParam
+            // Validate that function inputs implement FromCollected
             #(#input_checks)*
          };
 
@@ -5,6 +5,7 @@ use syn::spanned::Spanned;
 use syn::DeriveInput;
 
 use crate::attributes::{
+    parse_timeout,
     CollectAttrs,
     EnumVariantAttrs,
     RunAttrs,
@@ -55,21 +56,23 @@ fn expand_struct(
     let mut acc = darling::Error::accumulator();
 
     let type_ident = &attrs.ident;
+    let timeout = attrs
+        .timeout
+        .as_ref()
+        .and_then(|timeout| acc.handle(parse_timeout(timeout)));
     let span = attrs.run.span();
     let run_self = match &attrs.run {
         // We have a handler for this runnable, let's make sure we execute it.
         | Some(run) => {
+            let handler_call = gen_handler_call(
+                quote::quote!(Run),
+                run,
+                quote::quote!(stringify!(#type_ident)),
+                quote::quote!(_),
+                timeout,
+            );
             quote::quote_spanned! { span =>
-                ::cling::_private::tracing::log::debug!(
-                    target: "cling",
-                    "Running handler `{}` of type `{}`",
-                    stringify!(#run),
-                    stringify!(#type_ident),
-                );
-                {
-                    let effect = cling::_private::Handler::call(#run, args)?.into_effect().await?;
-                    effect.apply_effect(args);
-                }
+                #handler_call
             }
         }
         | None => quote::quote!(),
@@ -78,11 +81,17 @@ fn expand_struct(
     // collect _Collectable_ fields in CollectedArgs
     let mut collect_arguments = TokenStream::new();
     let mut subcommand_runs = TokenStream::new();
+    let mut completers = TokenStream::new();
+    let mut metadata = TokenStream::new();
     let mut found_subcommand = false;
     // We collect our own object in all cases.
     collect_arguments.extend(quote::quote! {
          if (self).as_collectable().can_collect() {
-             args.insert(self.clone(), false);
+             args.insert_with_debug(
+                 self.clone(),
+                 false,
+                 (&DebugProbe::<Self>::new()).debug_fn(),
+             );
          }
     });
     for field in &fields.fields {
@@ -95,6 +104,31 @@ fn expand_struct(
         // We only support named structs. darling validation will ensure this.
         let field_name = field.ident.clone().unwrap();
         let field_type = &field.ty;
+        if let Some(complete) = &field.complete {
+            if field.is_subcommand() {
+                acc.push(
+                    Error::custom(
+                        "Subcommand fields cannot have #[cling(complete = \
+                         ...)]",
+                    )
+                    .with_span(&complete.span()),
+                );
+            }
+            let span = complete.span();
+            let handler_call = gen_handler_call(
+                quote::quote!(Complete),
+                complete,
+                quote::quote!(stringify!(#type_ident)),
+                quote::quote!(::cling::_private::Candidates),
+                None,
+            );
+            // The field name is the clap argument id.
+            completers.extend(quote::quote_spanned! { span =>
+                if args.completes_arg(stringify!(#field_name)) {
+                    #handler_call
+                }
+            });
+        }
         if field.is_subcommand() {
             let span = field.ty.span();
             found_subcommand = true;
@@ -102,54 +136,71 @@ fn expand_struct(
             subcommand_runs.extend(quote::quote_spanned! { span =>
                 <dyn ::cling::prelude::Run>::call(&self.#field_name, args).await?;
             });
+            metadata.extend(quote::quote_spanned! { span =>
+                metadata = metadata.subcommands_of(
+                    <#field_type as ::cling::prelude::Run>::metadata(),
+                );
+            });
         } else {
             // Escape hatch if this particular field is problematic.
             if field.skip {
                 collect_arguments.extend(quote::quote! {
-                    ::cling::_private::tracing::log::debug!(
+                    ::cling::_private::tracing::debug!(
                         target: "cling",
-                        "Skipping `{}.{}` because it's marked with `#[cling(skip)]`",
-                        stringify!(#type_ident),
-                        stringify!(#field_name),
+                        field = stringify!(#field_name),
+                        "Skipping field marked with `#[cling(skip)]`",
                     );
                 });
             } else if field.collect {
                 // If the field is marked with #[cling(collect)], we will wrap
                 // it in Collected<T> and store it wrapped.
                 collect_arguments.extend(quote::quote! {
-                    ::cling::_private::tracing::log::debug!(
+                    ::cling::_private::tracing::debug!(
                         target: "cling",
-                        "Collecting type `{}` from `{}.{}` \
-                            because it's marked with `#[cling(collect)]`. \
-                            This can be extracted with cling::prelude::Collected<T> at runtime.",
-                        stringify!(#field_type),
-                        stringify!(#type_ident),
-                        stringify!(#field_name),
+                        field = stringify!(#field_name),
+                        field_type = stringify!(#field_type),
+                        "Collecting field marked with `#[cling(collect)]` as `Collected<T>`",
+                    );
+                    args.insert_with_debug(
+                        ::cling::prelude::Collected(self.#field_name.clone()),
+                        false,
+                        (&DebugProbe::<::cling::prelude::Collected<#field_type>>::new()).debug_fn(),
+                    );
+                });
+                metadata.extend(quote::quote! {
+                    metadata = metadata.collects(
+                        std::any::type_name::<::cling::prelude::Collected<#field_type>>(),
                     );
-                    args.insert(::cling::prelude::Collected(self.#field_name.clone()), false);
                 });
             } else {
                 // Not a subcommand, let's see if we should collect it.
                 collect_arguments.extend(quote::quote! {
                     if (&self.#field_name).as_collectable().can_collect() {
-                        ::cling::_private::tracing::log::debug!(
+                        ::cling::_private::tracing::debug!(
                             target: "cling",
-                            "Collecting type `{}` from `{}.{}` because it derives `Collect`",
-                            stringify!(#field_type),
-                            stringify!(#type_ident),
-                            stringify!(#field_name),
+                            field = stringify!(#field_name),
+                            field_type = stringify!(#field_type),
+                            "Collecting field that implements `Collect`",
+                        );
+                        args.insert_with_debug(
+                            self.#field_name.clone(),
+                            false,
+                            (&DebugProbe::<#field_type>::new()).debug_fn(),
                         );
-                        args.insert(self.#field_name.clone(), false);
                     } else {
-                        ::cling::_private::tracing::log::trace!(
+                        ::cling::_private::tracing::trace!(
                             target: "cling",
-                            "Skipping `{}.{}` because `{}` doesn't implement `Collect`",
-                            stringify!(#type_ident),
-                            stringify!(#field_name),
-                            stringify!(#field_type),
+                            field = stringify!(#field_name),
+                            field_type = stringify!(#field_type),
+                            "Skipping field that doesn't implement `Collect`",
                         );
                     }
                 });
+                metadata.extend(quote::quote! {
+                    if (&CollectProbe::<#field_type>::new()).is_collectable() {
+                        metadata = metadata.collects(std::any::type_name::<#field_type>());
+                    }
+                });
             }
         }
     }
@@ -166,16 +217,102 @@ fn expand_struct(
         ));
         return acc.finish_with(TokenStream::new());
     }
+    let run_after = match &attrs.after {
+        | Some(after) => {
+            let span = after.span();
+            let handler_call = gen_handler_call(
+                quote::quote!(After),
+                after,
+                quote::quote!(stringify!(#type_ident)),
+                quote::quote!(_),
+                timeout,
+            );
+            quote::quote_spanned! { span =>
+                #handler_call
+            }
+        }
+        | None => quote::quote!(),
+    };
+
+    let body = match &attrs.finally {
+        | Some(finally) => {
+            let span = finally.span();
+            let handler_call = gen_handler_call(
+                quote::quote!(Finally),
+                finally,
+                quote::quote!(stringify!(#type_ident)),
+                quote::quote!(_),
+                timeout,
+            );
+            quote::quote_spanned! { span =>
+                let outcome: std::result::Result<(), cling::prelude::CliError> = async {
+                    // run self if run attribute is defined
+                    #run_self
+                    // run subcommands if any
+                    #subcommand_runs
+                    // run after handler if subcommands succeeded
+                    #run_after
+                    Ok(())
+                }.await;
+                // The outcome is made available to the finally handler as
+                // `&Result<(), CliError>`.
+                let outcome = Outcome::provide(args, outcome);
+                let finally_outcome: std::result::Result<(), cling::prelude::CliError> = async {
+                    #handler_call
+                    Ok(())
+                }.await;
+                let outcome = Outcome::take(args, outcome);
+                // The command's error takes precedence over the finally
+                // handler's error.
+                outcome?;
+                finally_outcome?;
+            }
+        }
+        | None => {
+            quote::quote! {
+                // run self if run attribute is defined
+                #run_self
+                // run subcommands if any
+                #subcommand_runs
+                // run after handler if subcommands succeeded
+                #run_after
+            }
+        }
+    };
+
+    let handlers_metadata = [
+        (quote::quote!(Run), &attrs.run),
+        (quote::quote!(After), &attrs.after),
+        (quote::quote!(Finally), &attrs.finally),
+    ]
+    .into_iter()
+    .filter_map(|(kind, handler)| {
+        let handler = handler.as_ref()?;
+        Some(gen_handler_metadata(kind, handler))
+    });
+    let metadata = quote::quote! {
+        let mut metadata = RunMetadata::new(stringify!(#type_ident));
+        if (&CollectProbe::<Self>::new()).is_collectable() {
+            metadata = metadata.collects(std::any::type_name::<Self>());
+        }
+        #metadata
+        #(metadata = metadata #handlers_metadata;)*
+        metadata
+    };
+
     // runnable_impl
     let impl_runnable = gen_runnable_impl(
         attrs,
+        metadata,
         quote::quote! {
-            // Collect fields that are collectable
-            #collect_arguments
-            // run self if run attribute is defined
-            #run_self
-            // run subcommands if any
-            #subcommand_runs
+            // Collect fields that are collectable, unless they were collected
+            // by a previous command of the session (e.g. in a REPL scope).
+            if !args.is_resumed_at(args.level()) {
+                #collect_arguments
+            }
+            // Complete the value of a field if requested
+            #completers
+            #body
         },
     );
 
@@ -194,32 +331,90 @@ fn expand_enum(
              and/or derive Run for the variant newtype argument",
         ));
     }
+    if attrs.after.is_some() {
+        return Err(Error::custom(
+            "Runnable enum cannot have a #[cling(after = ...)] attribute. \
+             Please derive Run for the variant newtype argument and mark it \
+             with #[cling(after = ...)] instead",
+        ));
+    }
+    if attrs.finally.is_some() {
+        return Err(Error::custom(
+            "Runnable enum cannot have a #[cling(finally = ...)] attribute. \
+             Please derive Run for the variant newtype argument and mark it \
+             with #[cling(finally = ...)] instead",
+        ));
+    }
+
+    if let Some(timeout) = &attrs.timeout {
+        return Err(Error::custom(
+            "Runnable enum cannot have a #[cling(timeout = ...)] attribute. \
+             Please mark the unit variants with #[cling(timeout = ...)] \
+             instead and/or add it to the variant newtype argument",
+        )
+        .with_span(timeout));
+    }
 
     let mut acc = darling::Error::accumulator();
 
     let mut variant_tokens = Vec::with_capacity(variants.len());
+    let mut variant_metadata = Vec::with_capacity(variants.len());
+    let mut variant_commands = Vec::with_capacity(variants.len());
 
     let enum_name = &attrs.ident;
+    let rename_all = attrs.rename_all();
     for variant in variants {
         let span = variant.ident.span();
         let variant_name = &variant.ident;
+        // The name of the subcommand in the command path, flattened variants
+        // don't add one.
+        let command = if variant.is_flatten() {
+            quote::quote!(None)
+        } else {
+            let subcommand_name =
+                variant.subcommand_name(rename_all.as_deref());
+            quote::quote!(Some(#subcommand_name))
+        };
+        variant_commands.push(quote::quote! {
+            #enum_name::#variant_name { .. } => #command,
+        });
         if variant.fields.is_empty() {
+            let timeout = variant
+                .timeout
+                .as_ref()
+                .and_then(|timeout| acc.handle(parse_timeout(timeout)));
             // We must have a #[cling(run = ...)] attribute.
             match &variant.run {
                 | Some(run) => {
-                    variant_tokens.push(quote::quote_spanned! { span =>
-                        #enum_name::#variant_name => {
-                            ::cling::_private::tracing::log::debug!(
-                                target: "cling",
-                                "Running handler `{}` of variant `{}::{}`",
-                                stringify!(#run),
+                    let handler_call = gen_handler_call(
+                        quote::quote!(Run),
+                        run,
+                        quote::quote!(concat!(
+                            stringify!(#enum_name),
+                            "::",
+                            stringify!(#variant_name),
+                        )),
+                        quote::quote!(_),
+                        timeout,
+                    );
+                    let subcommand_name =
+                        variant.subcommand_name(rename_all.as_deref());
+                    let handler_metadata =
+                        gen_handler_metadata(quote::quote!(Run), run);
+                    variant_metadata.push(quote::quote! {
+                        metadata = metadata.subcommand(
+                            #subcommand_name,
+                            RunMetadata::new(concat!(
                                 stringify!(#enum_name),
+                                "::",
                                 stringify!(#variant_name),
-                            );
-                            {
-                                let effect = cling::_private::Handler::call(#run, args)?.into_effect().await?;
-                                effect.apply_effect(args);
-                            }
+                            ))
+                            #handler_metadata,
+                        );
+                    });
+                    variant_tokens.push(quote::quote_spanned! { span =>
+                        #enum_name::#variant_name => {
+                            #handler_call
                         }
                     });
                 }
@@ -245,7 +440,37 @@ fn expand_enum(
                 ))
                 .with_span(&variant.run),
             );
+        } else if let Some(timeout) = &variant.timeout {
+            let var_inner_ty =
+                &variant.fields.fields.first().expect("non-empty variant").ty;
+            let var_inner_ty = quote::quote!(#var_inner_ty).to_string();
+            acc.push(
+                Error::custom(format!(
+                    "Non-unit enums cannot have #[cling(timeout = ...)]. \
+                     Instead, add it to the variant inner type `{}`.",
+                    var_inner_ty,
+                ))
+                .with_span(timeout),
+            );
         } else {
+            let var_inner_ty =
+                &variant.fields.fields.first().expect("non-empty variant").ty;
+            if variant.is_flatten() {
+                variant_metadata.push(quote::quote! {
+                    metadata = metadata.subcommands_of(
+                        <#var_inner_ty as ::cling::prelude::Run>::metadata(),
+                    );
+                });
+            } else {
+                let subcommand_name =
+                    variant.subcommand_name(rename_all.as_deref());
+                variant_metadata.push(quote::quote! {
+                    metadata = metadata.subcommand(
+                        #subcommand_name,
+                        <#var_inner_ty as ::cling::prelude::Run>::metadata(),
+                    );
+                });
+            }
             // We will dispatch to the newtype assuming that it's Run
             variant_tokens.push(quote::quote_spanned! { span =>
                 #enum_name::#variant_name(sub) => {
@@ -257,16 +482,107 @@ fn expand_enum(
     let tokens = gen_runnable_impl(
         attrs,
         quote::quote! {
-            match self {
-                #(#variant_tokens)*
+            let mut metadata = RunMetadata::new(stringify!(#enum_name));
+            #(#variant_metadata)*
+            metadata
+        },
+        quote::quote! {
+            // Variants are subcommands, they run one level deeper.
+            let level = args.level();
+            args.set_level(level + 1);
+            let command: Option<&'static str> = match self {
+                #(#variant_commands)*
+            };
+            if let Some(command) = command {
+                args.push_command(command);
+            }
+            let outcome: std::result::Result<(), cling::prelude::CliError> = async {
+                match self {
+                    #(#variant_tokens)*
+                }
+                Ok(())
+            }.await;
+            if command.is_some() {
+                args.pop_command();
             }
+            args.set_level(level);
+            outcome?;
         },
     );
 
     acc.finish_with(tokens)
 }
 
-fn gen_runnable_impl(attrs: &RunAttrs, impl_body: TokenStream) -> TokenStream {
+/// Calls a handler through the layers registered at runtime and applies its
+/// effect.
+fn gen_handler_call(
+    kind: TokenStream,
+    handler: &syn::Path,
+    type_name: TokenStream,
+    effect_type: TokenStream,
+    timeout: Option<u64>,
+) -> TokenStream {
+    let call = match timeout {
+        | Some(millis) => {
+            quote::quote! {
+                ::cling::_private::timeout(
+                    timer,
+                    ::std::time::Duration::from_millis(#millis),
+                    stringify!(#handler),
+                    future,
+                ).await?
+            }
+        }
+        | None => quote::quote! { future.await? },
+    };
+    // The effect context is captured before calling the handler since its
+    // parameters borrow `args` until the effect is produced.
+    let call = quote::quote! {{
+        let timer = args.timer();
+        let context = ::cling::_private::EffectContext::capture(args);
+        let future = {
+            let args = &mut *args;
+            context.scope(async move {
+                cling::_private::Handler::call(#handler, args)?.into_effect().await
+            })
+        };
+        #call
+    }};
+    quote::quote! {
+        ::cling::_private::invoke(
+            args,
+            ::cling::_private::Invocation::handler(
+                ::cling::_private::InvocationKind::#kind,
+                stringify!(#handler),
+                #type_name,
+                ::cling::_private::Handler::param_types(&#handler),
+            ),
+            |args| Box::pin(async move {
+                let effect: #effect_type = #call;
+                let debug = (&DebugProbe::of(&effect)).state_debug_fn();
+                effect.apply_effect_with_debug(args, debug);
+                Ok(())
+            }),
+        ).await?;
+    }
+}
+
+/// Builder call that adds a handler to a `RunMetadata`.
+fn gen_handler_metadata(kind: TokenStream, handler: &syn::Path) -> TokenStream {
+    quote::quote! {
+        .handler(
+            ::cling::_private::InvocationKind::#kind,
+            stringify!(#handler),
+            ::cling::_private::Handler::param_types(&#handler),
+        )
+    }
+}
+
+fn gen_runnable_impl(
+    attrs: &RunAttrs,
+    metadata_body: TokenStream,
+    impl_body: TokenStream,
+) -> TokenStream {
     let name = &attrs.ident;
     let generics = &attrs.generics;
     quote::quote! {
@@ -279,10 +595,20 @@ fn gen_runnable_impl(attrs: &RunAttrs, impl_body: TokenStream) -> TokenStream {
             ) ->  std::pin::Pin<Box<dyn std::future::Future<Output = std::result::Result<(), cling::prelude::CliError>> + Send + 'a>> {
                 use cling::_private::*;
 
-                Box::pin(async move {
-                    #impl_body
-                    Ok(())
-                })
+                invoke(
+                    args,
+                    Invocation::command(stringify!(#name)),
+                    move |args| Box::pin(async move {
+                        #impl_body
+                        Ok(())
+                    }),
+                )
+            }
+
+            fn metadata() -> ::cling::_private::RunMetadata {
+                use cling::_private::*;
+
+                #metadata_body
             }
         }
         ::cling::_private::static_assertions::assert_impl_all!(#name #generics: Clone);
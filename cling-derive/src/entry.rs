@@ -0,0 +1,38 @@
+use proc_macro2::TokenStream;
+use syn::spanned::Spanned;
+use syn::ItemFn;
+
+pub fn expand_main(args: TokenStream, mut input: ItemFn) -> TokenStream {
+    if !args.is_empty() {
+        return syn::Error::new(
+            args.span(),
+            "#[cling::main] doesn't accept arguments, enable the `tokio` \
+             feature of cling to run on tokio",
+        )
+        .into_compile_error();
+    }
+    if input.sig.asyncness.is_none() {
+        return syn::Error::new(
+            input.sig.fn_token.span(),
+            "the `async` keyword is missing from the function declaration",
+        )
+        .into_compile_error();
+    }
+    if !input.sig.inputs.is_empty() {
+        return syn::Error::new(
+            input.sig.inputs.span(),
+            "#[cling::main] functions cannot accept arguments",
+        )
+        .into_compile_error();
+    }
+    input.sig.asyncness = None;
+    let body = &input.block;
+    let span = body.span();
+    let block = quote::quote_spanned! { span =>
+        {
+            ::cling::_private::block_on_main(async move #body)
+        }
+    };
+    input.block = syn::parse_quote!(#block);
+    quote::quote! { #input }
+}
@@ -5,9 +5,10 @@ mod attributes;
 #[cfg(debug_assertions)]
 mod cling_handler;
 mod derives;
+mod entry;
 
 use proc_macro::TokenStream;
-use syn::{parse_macro_input, DeriveInput};
+use syn::{parse_macro_input, DeriveInput, ItemFn};
 
 #[proc_macro_derive(Run, attributes(cling, command, clap))]
 pub fn derive_run(input: TokenStream) -> TokenStream {
@@ -28,8 +29,13 @@ pub fn cling_handler(_attr: TokenStream, function: TokenStream) -> TokenStream {
 
     #[cfg(debug_assertions)]
     {
-        use syn::ItemFn;
         let input = parse_macro_input!(function as ItemFn);
         cling_handler::expand_handler(&input).into()
     }
 }
+
+#[proc_macro_attribute]
+pub fn main(args: TokenStream, function: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(function as ItemFn);
+    entry::expand_main(args.into(), input).into()
+}
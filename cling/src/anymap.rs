@@ -1,5 +1,6 @@
 use std::any::{type_name, Any, TypeId};
-use std::collections::{HashMap, HashSet};
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
 use std::hash::{BuildHasherDefault, Hasher};
 
 // Copied/inspired from Axum's extensions.
@@ -28,13 +29,28 @@ impl Hasher for IdHasher {
 /// Holds values of different types and accessed by TypeId.
 #[derive(Default)]
 pub struct AnyMap {
-    // Key is TypeId, value is heap-allocated Box<dyn Any + Send + Sync>
-    map: HashMap<
-        TypeId,
-        Box<dyn Any + Send + Sync>,
-        BuildHasherDefault<IdHasher>,
-    >,
-    known_types: HashSet<String>,
+    // Key is TypeId, value is heap-allocated Box<dyn Any + Send + Sync> along
+    // with the name of its type.
+    map: HashMap<TypeId, Entry, BuildHasherDefault<IdHasher>>,
+}
+
+/// Formats a value of the map with the `Debug` implementation of its type.
+pub type DebugFn =
+    fn(&(dyn Any + Send + Sync), &mut Formatter<'_>) -> fmt::Result;
+
+struct Entry {
+    value: Box<dyn Any + Send + Sync>,
+    type_name: &'static str,
+    debug: Option<DebugFn>,
+}
+
+impl Debug for Entry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.debug {
+            | Some(debug) => debug(&*self.value, f),
+            | None => f.write_str(".."),
+        }
+    }
 }
 
 impl AnyMap {
@@ -44,32 +60,100 @@ impl AnyMap {
             .get(&TypeId::of::<T>())
             // downcast_ref returns a reference to the boxed value if it is of
             // type T.
-            .and_then(|boxed| (&**boxed as &(dyn Any + 'static)).downcast_ref())
+            .and_then(|entry| {
+                (&*entry.value as &(dyn Any + 'static)).downcast_ref()
+            })
     }
 
     /// Returns a mutable reference to value of type T if exists.
     pub fn get_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
-        self.map.get_mut(&TypeId::of::<T>()).and_then(|boxed| {
-            (&mut **boxed as &mut (dyn Any + 'static)).downcast_mut()
+        self.map.get_mut(&TypeId::of::<T>()).and_then(|entry| {
+            (&mut *entry.value as &mut (dyn Any + 'static)).downcast_mut()
         })
     }
 
     /// Inserts a value into the collected arguments. If the value already
     /// exists, it will be returned.
+    #[cfg(test)]
     pub fn insert<T: Send + Sync + 'static>(&mut self, val: T) -> Option<T> {
-        self.known_types.insert(type_name::<T>().to_string());
+        self.insert_with_debug(val, None)
+    }
+
+    /// Inserts a value and returns the previous value of the same type, if
+    /// any. `debug` formats the value in [AnyMap::debug_values], the formatter
+    /// of the previous value is kept if `debug` is `None`.
+    pub fn insert_with_debug<T: Send + Sync + 'static>(
+        &mut self,
+        val: T,
+        debug: Option<DebugFn>,
+    ) -> Option<T> {
+        let previous_debug = self
+            .map
+            .get(&TypeId::of::<T>())
+            .and_then(|entry| entry.debug);
+        let entry = Entry {
+            value: Box::new(val),
+            type_name: type_name::<T>(),
+            debug: debug.or(previous_debug),
+        };
         self.map
-            .insert(TypeId::of::<T>(), Box::new(val))
-            .and_then(|boxed| {
-                (boxed as Box<dyn Any + 'static>)
-                    .downcast()
-                    .ok()
-                    .map(|boxed| *boxed)
-            })
+            .insert(TypeId::of::<T>(), entry)
+            .and_then(|entry| downcast(entry.value))
+    }
+
+    /// Removes the value of type T if exists and returns it.
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.map
+            .remove(&TypeId::of::<T>())
+            .and_then(|entry| downcast(entry.value))
+    }
+
+    /// Moves the values whose type matches `predicate` into a new map.
+    pub fn split_off(&mut self, predicate: impl Fn(&TypeId) -> bool) -> AnyMap {
+        let keys: Vec<TypeId> = self
+            .map
+            .keys()
+            .filter(|id| predicate(id))
+            .copied()
+            .collect();
+        let mut other = AnyMap::default();
+        for key in keys {
+            if let Some(entry) = self.map.remove(&key) {
+                other.map.insert(key, entry);
+            }
+        }
+        other
+    }
+
+    /// Moves the values of `other` into this map, replacing values of the
+    /// same type.
+    #[cfg(feature = "repl")]
+    pub fn absorb(&mut self, other: AnyMap) {
+        self.map.extend(other.map);
+    }
+
+    #[cfg(feature = "repl")]
+    pub fn type_ids(&self) -> std::collections::HashSet<TypeId> {
+        self.map.keys().copied().collect()
     }
 
     pub fn known_types(&self) -> Vec<String> {
-        self.known_types.iter().cloned().collect()
+        self.map
+            .values()
+            .map(|entry| entry.type_name.to_string())
+            .collect()
+    }
+
+    /// Type names and `Debug` representations of the values, sorted by type
+    /// name. Values inserted without a formatter are shown as `..`.
+    pub fn debug_values(&self) -> Vec<(&'static str, String)> {
+        let mut values: Vec<_> = self
+            .map
+            .values()
+            .map(|entry| (entry.type_name, format!("{:?}", entry)))
+            .collect();
+        values.sort();
+        values
     }
 
     #[inline]
@@ -88,6 +172,24 @@ impl AnyMap {
     }
 }
 
+/// A [DebugFn] for values of type `T`.
+pub fn debug_fn<T: Debug + 'static>(
+    value: &(dyn Any + Send + Sync),
+    f: &mut Formatter<'_>,
+) -> fmt::Result {
+    match (value as &dyn Any).downcast_ref::<T>() {
+        | Some(value) => value.fmt(f),
+        | None => f.write_str(".."),
+    }
+}
+
+fn downcast<T: 'static>(boxed: Box<dyn Any + Send + Sync>) -> Option<T> {
+    (boxed as Box<dyn Any + 'static>)
+        .downcast()
+        .ok()
+        .map(|boxed| *boxed)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -115,6 +217,28 @@ mod tests {
 
         assert_eq!(MyType(42), map.insert(MyType(43)).unwrap());
 
+        assert_eq!(MyType(43), map.remove::<MyType>().unwrap());
+        assert_eq!(None, map.remove::<MyType>());
+        assert_eq!(2, map.len());
+
+        let strings = map.split_off(|id| *id == TypeId::of::<String>());
+        assert_eq!(1, map.len());
+        assert_eq!(1, strings.len());
+        assert_eq!("hello", *strings.get::<String>().unwrap());
+        assert_eq!(None, map.get::<String>());
+        map.insert("hello".to_string());
+
+        map.insert_with_debug(MyType(1), Some(debug_fn::<MyType>));
+        map.insert(MyType(2));
+        assert_eq!(
+            vec![
+                ("alloc::string::String", "..".to_owned()),
+                (type_name::<MyType>(), "MyType(2)".to_owned()),
+                ("u32", "..".to_owned()),
+            ],
+            map.debug_values()
+        );
+
         map.clear();
 
         assert_eq!(0, map.len());
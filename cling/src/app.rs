@@ -1,12 +1,24 @@
 //! The main entry point for the cling framework.
+use std::io::Write;
 use std::marker::PhantomData;
 use std::process::{ExitCode, Termination};
+use std::sync::Arc;
 
 use clap::Parser;
 
 use super::error::{format_clap_error, CliErrorHandler};
+use crate::cancellation::Cancellation;
+use crate::debug::PrintCollected;
+use crate::dry_run::DryRun;
+use crate::environment::Environment;
 use crate::error::CliError;
+use crate::executor::block_on;
+use crate::layer::Layer;
+use crate::metadata::RunMetadata;
 use crate::params::CollectedArgs;
+use crate::plan::Plan;
+use crate::report::ExecutionReport;
+use crate::timeout::Timer;
 
 mod _private {
     pub struct Build;
@@ -24,6 +36,14 @@ pub trait Run: Send + Sync {
     ) -> std::pin::Pin<
         Box<dyn std::future::Future<Output = Result<(), CliError>> + Send + 'a>,
     >;
+
+    /// Describes the handlers and collected types of this command.
+    fn metadata() -> RunMetadata
+    where
+        Self: Sized,
+    {
+        RunMetadata::new(std::any::type_name::<Self>())
+    }
 }
 
 type ClingReady<T> = Cling<T, Ready>;
@@ -87,18 +107,34 @@ pub struct Cling<T, S = Build> {
 }
 
 /// Holds configuration for cling framework.
-#[allow(dead_code)]
 #[derive(Default, Clone)]
-struct Settings {}
+pub(crate) struct Settings {
+    pub(crate) layers: Vec<Arc<dyn Layer>>,
+    pub(crate) timer: Option<Arc<dyn Timer>>,
+    pub(crate) dry_run: bool,
+    pub(crate) debug_collected: bool,
+    pub(crate) record_execution: bool,
+    // Grace period after a shutdown signal, if signals are handled.
+    #[cfg(feature = "signals")]
+    pub(crate) grace_period: Option<std::time::Duration>,
+}
 
 enum ClingInner<T> {
     Ready {
         parsed: T,
         collected_params: CollectedArgs,
     },
+    // Started by a completion script to complete `words` instead of parsing
+    // them.
+    #[cfg(feature = "completions")]
+    Completing {
+        words: Vec<String>,
+        collected_params: CollectedArgs,
+    },
     Finished {
         result: Result<(), CliError>,
         collected_params: CollectedArgs,
+        execution: ExecutionReport,
         _parsed_type: PhantomData<T>,
     },
 }
@@ -108,12 +144,21 @@ impl<T: Run + Parser> Cling<T, Finished> {
     /// when you want to return a successful Cling instance from `main()`
     /// directly.
     pub fn success() -> ClingFinished<T> {
+        Self::with_result(Settings::default(), Ok(()), CollectedArgs::new())
+    }
+
+    pub(crate) fn with_result(
+        settings: Settings,
+        result: Result<(), CliError>,
+        collected_params: CollectedArgs,
+    ) -> ClingFinished<T> {
         ClingFinished {
-            settings: Settings::default(),
+            settings,
             _status: PhantomData,
             inner: ClingInner::Finished {
-                result: Ok(()),
-                collected_params: CollectedArgs::new(),
+                result,
+                collected_params,
+                execution: ExecutionReport::default(),
                 _parsed_type: PhantomData,
             },
         }
@@ -123,15 +168,11 @@ impl<T: Run + Parser> Cling<T, Finished> {
     /// when you want to wrap an Error into a Cling instance to be returned from
     /// `main()`.
     pub fn failed(e: impl Into<CliError>) -> ClingFinished<T> {
-        ClingFinished {
-            settings: Settings::default(),
-            _status: PhantomData,
-            inner: ClingInner::Finished {
-                result: Err(e.into()),
-                collected_params: CollectedArgs::new(),
-                _parsed_type: PhantomData,
-            },
-        }
+        Self::with_result(
+            Settings::default(),
+            Err(e.into()),
+            CollectedArgs::new(),
+        )
     }
 }
 
@@ -139,38 +180,55 @@ impl<T: Run + Parser> Cling<T, Finished> {
 impl<T: Run + Parser> Cling<T, Build> {
     /// Create a Cling application from a parsed clap struct.
     pub fn new(parsed: T) -> ClingReady<T> {
-        ClingReady {
-            settings: Settings::default(),
-            _status: PhantomData,
-            inner: ClingInner::Ready {
-                parsed,
-                collected_params: CollectedArgs::new(),
-            },
-        }
+        Self::with_settings(parsed, Settings::default(), CollectedArgs::new())
     }
 
     /// [Provisional]
-    #[allow(dead_code)]
-    fn with_settings(parsed: T, settings: Settings) -> ClingReady<T> {
+    pub(crate) fn with_settings(
+        parsed: T,
+        settings: Settings,
+        collected_params: CollectedArgs,
+    ) -> ClingReady<T> {
         ClingReady {
             settings,
             _status: PhantomData,
             inner: ClingInner::Ready {
                 parsed,
-                collected_params: CollectedArgs::new(),
+                collected_params,
             },
         }
     }
 
+    /// Parses the command line of the process.
+    ///
+    /// All the entry points that parse the command line of the process go
+    /// through here. If the process was started by a completion script of
+    /// `Cling::generate_completions()`, the command line is not parsed, and
+    /// running the app prints the completion candidates instead.
+    fn parse_env() -> Result<ClingReady<T>, clap::Error> {
+        #[cfg(feature = "completions")]
+        if let Some(words) = crate::complete::env_words() {
+            return Ok(ClingReady {
+                settings: Settings::default(),
+                _status: PhantomData,
+                inner: ClingInner::Completing {
+                    words,
+                    collected_params: CollectedArgs::new(),
+                },
+            });
+        }
+        <T as clap::Parser>::try_parse()
+            .map(Self::new)
+            .map_err(format_clap_error::<T>)
+    }
+
     /// Parse command line arguments, run the program, and return the finished
     /// Cling application. [[`ClingFinished<T>`]] can be returned from `main()`
     /// directly which will handle printing errors and exiting with the
     /// correct exit code.
     pub async fn parse_and_run() -> ClingFinished<T> {
-        let parsed =
-            <T as clap::Parser>::try_parse().map_err(format_clap_error::<T>);
-        match parsed {
-            | Ok(parsed) => Cling::new(parsed).run().await,
+        match Self::parse_env() {
+            | Ok(cling) => cling.run().await,
             | Err(e) => {
                 ClingFinished {
                     settings: Settings::default(),
@@ -178,6 +236,7 @@ impl<T: Run + Parser> Cling<T, Build> {
                     inner: ClingInner::Finished {
                         result: Err(e.into()),
                         collected_params: CollectedArgs::new(),
+                        execution: ExecutionReport::default(),
                         _parsed_type: PhantomData,
                     },
                 }
@@ -187,28 +246,13 @@ impl<T: Run + Parser> Cling<T, Build> {
 
     /// Parse command line arguments and exit if parsing failed.
     pub fn parse() -> ClingReady<T> {
-        ClingReady {
-            settings: Settings::default(),
-            _status: PhantomData,
-            inner: ClingInner::Ready {
-                parsed: <T as clap::Parser>::parse(),
-                collected_params: CollectedArgs::new(),
-            },
-        }
+        Self::parse_env().unwrap_or_else(|e| e.exit())
     }
 
     /// Attempt to parse command line arguments and return a runnable Cling
     /// application.
     pub fn try_parse() -> Result<ClingReady<T>, CliError> {
-        Ok(ClingReady {
-            settings: Settings::default(),
-            _status: PhantomData,
-            inner: ClingInner::Ready {
-                parsed: <T as clap::Parser>::try_parse()
-                    .map_err(format_clap_error::<T>)?,
-                collected_params: CollectedArgs::new(),
-            },
-        })
+        Ok(Self::parse_env()?)
     }
 
     pub fn try_parse_from<I, B>(itr: I) -> Result<ClingReady<T>, CliError>
@@ -264,28 +308,108 @@ impl<T: Run + Parser> Cling<T, Build> {
 
     /// Parse command line arguments and aborts the program if parsing failed.
     pub fn parse_or_exit() -> ClingReady<T> {
-        ClingReady {
-            settings: Settings::default(),
-            _status: PhantomData,
-            inner: ClingInner::Ready {
-                parsed: <T as clap::Parser>::try_parse()
-                    .map_err(format_clap_error::<T>)
-                    .unwrap_or_exit(),
-                collected_params: CollectedArgs::new(),
-            },
-        }
+        Self::parse_env().unwrap_or_exit()
     }
 
     /// Parses command line arguments, runs the program and exits afterwards.
     pub async fn default_run_and_exit() -> ! {
         Self::parse_or_exit().run_and_exit().await
     }
+
+    /// Like [Cling::default_run_and_exit], but blocks the current thread
+    /// instead of requiring an async runtime.
+    pub fn default_run_and_exit_blocking() -> ! {
+        block_on(Self::default_run_and_exit())
+    }
 }
 
 /// Cling is now ready to run.
 impl<T: Run + Parser> Cling<T, Ready> {
+    /// Wraps every command dispatch and handler call in `layer`.
+    ///
+    /// Layers run in the order they were added, the first layer being the
+    /// outermost. See [Layer] for details.
+    pub fn layer(mut self, layer: impl Layer) -> Self {
+        self.settings.layers.push(Arc::new(layer));
+        self
+    }
+
+    /// Uses `timer` to enforce `#[cling(timeout = "...")]` instead of the
+    /// default [ThreadTimer](crate::ThreadTimer).
+    pub fn timer(mut self, timer: impl Timer) -> Self {
+        self.settings.timer = Some(Arc::new(timer));
+        self
+    }
+
+    /// Runs the command in [DryRun] mode, as if `--dry-run` was passed.
+    pub fn dry_run(mut self, enabled: bool) -> Self {
+        self.settings.dry_run = enabled;
+        self
+    }
+
+    /// Prints the type and `Debug` representation of every collected value
+    /// and state to stderr before each handler runs. Also enabled by setting
+    /// the `CLING_DEBUG` environment variable to `collected`.
+    ///
+    /// Values are printed as `..` if their type doesn't implement `Debug`,
+    /// and for the state passed to [Cling::run_with_state].
+    pub fn debug_collected(mut self, enabled: bool) -> Self {
+        self.settings.debug_collected = enabled;
+        self
+    }
+
+    /// Records the handlers that run, with their durations and outcomes, for
+    /// [ClingFinished::execution_report]. Also enabled by the `--timings`
+    /// flag of [TimingsArgs].
+    pub fn record_execution(mut self, enabled: bool) -> Self {
+        self.settings.record_execution = enabled;
+        self
+    }
+
+    /// Cancels the running command on SIGINT/SIGTERM (Ctrl-C on Windows)
+    /// instead of terminating the process.
+    ///
+    /// Handlers observe the signal through the [Cancellation] extractor. If
+    /// the command doesn't complete within `grace_period`, or if a second
+    /// signal arrives, the process exits with code 130. Otherwise, the run
+    /// fails with [CliError::Interrupted] unless the command has already
+    /// failed.
+    ///
+    /// Example:
+    /// ```rust, no_run
+    /// use std::time::Duration;
+    ///
+    /// use cling::prelude::*;
+    ///
+    /// #[derive(Run, Parser, Debug, Clone)]
+    /// #[cling(run = "serve")]
+    /// pub struct App {}
+    ///
+    /// async fn serve(cancellation: Cancellation) {
+    ///     cancellation.cancelled().await;
+    ///     println!("Flushing buffers before exiting...");
+    /// }
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> ClingFinished<App> {
+    ///     Cling::<App>::parse()
+    ///         .graceful_shutdown(Duration::from_secs(5))
+    ///         .run()
+    ///         .await
+    /// }
+    /// ```
+    #[cfg(feature = "signals")]
+    pub fn graceful_shutdown(
+        mut self,
+        grace_period: std::time::Duration,
+    ) -> Self {
+        self.settings.grace_period = Some(grace_period);
+        self
+    }
+
     pub async fn run_and_exit(self) -> ! {
         let res = self.run().await;
+        res.dry_run().print_plan();
         res.result().then_exit()
     }
 
@@ -293,54 +417,149 @@ impl<T: Run + Parser> Cling<T, Ready> {
     where
         S: Clone + Send + Sync + 'static,
     {
-        self.run_with_state(state).await.result().then_exit()
+        let res = self.run_with_state(state).await;
+        res.dry_run().print_plan();
+        res.result().then_exit()
     }
 
     /// Runs the app with a given state.
+    ///
+    /// If the process was started by a completion script, this prints the
+    /// completion candidates and exits instead.
     pub async fn run(self) -> ClingFinished<T> {
-        let ClingInner::Ready {
-            parsed,
-            mut collected_params,
-        } = self.inner
-        else {
+        let (parsed, mut collected_params) = match self.inner {
+            | ClingInner::Ready {
+                parsed,
+                collected_params,
+            } => (parsed, collected_params),
+            #[cfg(feature = "completions")]
+            | ClingInner::Completing {
+                words,
+                collected_params,
+            } => {
+                let completion = crate::complete::Completion::<T>::new(
+                    self.settings,
+                    Vec::new(),
+                );
+                // Boxed because completing runs the app again.
+                let candidates =
+                    Box::pin(completion.complete_with(words, collected_params))
+                        .await;
+                crate::complete::print_candidates_and_exit(candidates)
+            }
             // This will never happen. run() is only implemented on
             // Cling::Ready.
-            unreachable!()
+            | ClingInner::Finished { .. } => unreachable!(),
         };
 
+        let mut layers: Vec<Arc<dyn Layer>> = Vec::new();
+        let environment = collected_params.environment();
+        if self.settings.debug_collected
+            || debug_env_enables(&environment, "collected")
+        {
+            layers.push(Arc::new(PrintCollected));
+        }
+        layers.extend(self.settings.layers.iter().cloned());
+        collected_params.set_layers(layers);
+        if self.settings.record_execution {
+            collected_params.record_execution();
+        }
+        collected_params.set_timer(self.settings.timer.clone());
+        let cancellation = Cancellation::new();
+        collected_params.set_cancellation(cancellation.clone());
+        collected_params.set_dry_run(DryRun::new(self.settings.dry_run));
+        // The command path shown in `tracing` spans starts with the name of
+        // the binary.
+        collected_params.push_command(T::command().get_name());
+        #[cfg(feature = "signals")]
+        let shutdown = self.settings.grace_period.map(|grace_period| {
+            crate::signals::ShutdownGuard::install(cancellation, grace_period)
+        });
         let result = <T as Run>::call(&parsed, &mut collected_params).await;
+        // Handlers were planned instead of run because of `--explain`.
+        if let Some(plan) = collected_params.take_plan() {
+            let _ = write!(crate::console::stdout(), "{}", plan);
+        }
+        if collected_params.is_timed() {
+            let report = collected_params.execution_report();
+            let _ = write!(crate::console::stderr(), "{}", report);
+        }
+        #[cfg(feature = "signals")]
+        let result = match shutdown {
+            | Some(shutdown) if shutdown.interrupted() => {
+                result.and(Err(CliError::Interrupted))
+            }
+            | _ => result,
+        };
         // We ensure that transitioning to ClingFinished only happens when we
         // have a result. Therefore, it's safe to unwrap() the result in
         // ClingFinished.
+        let execution = collected_params.execution_report();
         ClingFinished {
             settings: self.settings,
             _status: PhantomData,
             inner: ClingInner::Finished {
                 collected_params,
                 result,
+                execution,
                 _parsed_type: PhantomData,
             },
         }
     }
 
+    /// Runs the app on the current thread with a minimal built-in executor,
+    /// for applications that don't use an async runtime.
+    ///
+    /// Handlers that depend on a runtime, e.g. on tokio's IO or timers, never
+    /// complete here.
+    pub fn run_blocking(self) -> ClingFinished<T> {
+        block_on(self.run())
+    }
+
+    /// Describes the handlers the command would run, in order, without
+    /// running them. Layers are not called either.
+    ///
+    /// This is also what the `--explain` flag of
+    /// [ExplainArgs](crate::ExplainArgs) prints.
+    pub async fn plan(self) -> Result<Plan, CliError> {
+        let (parsed, mut collected_params) = match self.inner {
+            | ClingInner::Ready {
+                parsed,
+                collected_params,
+            } => (parsed, collected_params),
+            // Completing the command line doesn't run any handlers.
+            #[cfg(feature = "completions")]
+            | ClingInner::Completing { .. } => return Ok(Plan::default()),
+            // This will never happen. plan() is only implemented on
+            // Cling::Ready.
+            | ClingInner::Finished { .. } => unreachable!(),
+        };
+        collected_params.start_planning();
+        <T as Run>::call(&parsed, &mut collected_params).await?;
+        Ok(collected_params.take_plan().unwrap_or_default())
+    }
+
     pub async fn run_with_state<S>(mut self, state: S) -> ClingFinished<T>
     where
         S: Clone + Send + Sync + 'static,
     {
-        let ClingInner::Ready {
-            ref mut collected_params,
-            ..
-        } = self.inner
-        else {
+        let collected_params = match self.inner {
+            | ClingInner::Ready {
+                ref mut collected_params,
+                ..
+            } => collected_params,
+            // Completion functions can extract the state as well.
+            #[cfg(feature = "completions")]
+            | ClingInner::Completing {
+                ref mut collected_params,
+                ..
+            } => collected_params,
             // This will never happen. run_with_state() is only implemented on
             // Cling::Ready.
-            unreachable!()
+            | ClingInner::Finished { .. } => unreachable!(),
         };
         // Put the state the state
-        collected_params.insert(
-            crate::extractors::State(state),
-            /* override_is_expected = */ true,
-        );
+        collected_params.insert_state(state);
         Self::run(self).await
     }
 }
@@ -369,6 +588,21 @@ impl<T: Run + Parser> Cling<T, Finished> {
         self.result_ref().is_err()
     }
 
+    /// The dry-run mode of the run, including the plan of changes that were
+    /// not made.
+    pub fn dry_run(&self) -> DryRun {
+        self.collected_parameters().dry_run()
+    }
+
+    /// The handlers that ran, with their durations and outcomes. Empty unless
+    /// [Cling::record_execution] or `--timings` enabled recording.
+    pub fn execution_report(&self) -> ExecutionReport {
+        let ClingInner::Finished { ref execution, .. } = self.inner else {
+            unreachable!()
+        };
+        execution.clone()
+    }
+
     pub fn collected_parameters(&self) -> &CollectedArgs {
         let ClingInner::Finished {
             ref collected_params,
@@ -380,6 +614,29 @@ impl<T: Run + Parser> Cling<T, Finished> {
         collected_params
     }
 
+    /// Prints the dry-run plan and the error, if any, returning the exit code
+    /// and the collected arguments.
+    pub(crate) fn print_report(self) -> (u8, CollectedArgs) {
+        self.dry_run().print_plan();
+        let ClingInner::Finished {
+            result,
+            collected_params,
+            ..
+        } = self.inner
+        else {
+            unreachable!()
+        };
+        let exit_code = match result {
+            | Ok(()) => 0,
+            | Err(e) => {
+                // Silently ignore IO errors.
+                let _ = e.print();
+                e.exit_code()
+            }
+        };
+        (exit_code, collected_params)
+    }
+
     pub fn collected_arguments_mut(&mut self) -> &mut CollectedArgs {
         let ClingInner::Finished {
             ref mut collected_params,
@@ -439,12 +696,8 @@ where
 /// if any.
 impl<T: Run + Parser> Termination for ClingFinished<T> {
     fn report(self) -> ExitCode {
-        if let Err(e) = self.result() {
-            // Silently ignore IO errors.
-            let _ = e.print();
-            return ExitCode::from(e.exit_code());
-        }
-        ExitCode::SUCCESS
+        let (exit_code, _) = self.print_report();
+        ExitCode::from(exit_code)
     }
 }
 
@@ -454,3 +707,11 @@ impl<T: Run + Parser> From<CliError> for ClingFinished<T> {
         Cling::failed(value)
     }
 }
+
+/// Whether `topic` is one of the comma-separated values of the `CLING_DEBUG`
+/// environment variable.
+fn debug_env_enables(environment: &Environment, topic: &str) -> bool {
+    environment
+        .var("CLING_DEBUG")
+        .is_ok_and(|value| value.split(',').any(|t| t.trim() == topic))
+}
@@ -0,0 +1,116 @@
+//! Cooperative cancellation of the running command.
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::params::{CollectedArgs, FromCollected};
+
+/// Extractor for the cancellation signal of the running command.
+///
+/// Long-running handlers can poll [Cancellation::is_cancelled] or await
+/// [Cancellation::cancelled] to stop their work gracefully, e.g. when the
+/// user presses Ctrl-C and the application was started with
+/// `graceful_shutdown` (requires the `signals` feature). Handlers and layers
+/// can also cancel the command themselves with [Cancellation::cancel].
+///
+/// Example:
+/// ```rust
+/// use cling::prelude::*;
+///
+/// async fn sync_files(cancellation: Cancellation) -> Result<(), CliError> {
+///     for file in ["a.txt", "b.txt"] {
+///         if cancellation.is_cancelled() {
+///             println!("Stopped before {}", file);
+///             break;
+///         }
+///         println!("Syncing {}", file);
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Clone, Default)]
+pub struct Cancellation {
+    inner: Arc<Inner>,
+}
+
+#[derive(Default)]
+struct Inner {
+    cancelled: AtomicBool,
+    wakers: Mutex<Vec<Waker>>,
+}
+
+impl Cancellation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Cancels the command. Wakes up all tasks waiting in
+    /// [Cancellation::cancelled].
+    pub fn cancel(&self) {
+        if self.inner.cancelled.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let wakers = std::mem::take(&mut *self.inner.wakers.lock().unwrap());
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Completes when the command is cancelled.
+    pub fn cancelled(&self) -> Cancelled {
+        Cancelled {
+            cancellation: self.clone(),
+        }
+    }
+}
+
+impl std::fmt::Debug for Cancellation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Cancellation")
+            .field("cancelled", &self.is_cancelled())
+            .finish()
+    }
+}
+
+impl<'a> FromCollected<'a> for Cancellation {
+    type Rejection = Infallible;
+
+    fn from_collected(args: &'a CollectedArgs) -> Result<Self, Infallible> {
+        // Commands that are not run by Cling (e.g. in tests) are never
+        // cancelled.
+        Ok(args.cancellation().cloned().unwrap_or_default())
+    }
+}
+
+/// Future returned by [Cancellation::cancelled].
+#[must_use = "futures do nothing unless awaited"]
+pub struct Cancelled {
+    cancellation: Cancellation,
+}
+
+impl Future for Cancelled {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let inner = &self.cancellation.inner;
+        if inner.cancelled.load(Ordering::SeqCst) {
+            return Poll::Ready(());
+        }
+        let mut wakers = inner.wakers.lock().unwrap();
+        // Check again, `cancel()` might have drained the wakers already.
+        if inner.cancelled.load(Ordering::SeqCst) {
+            return Poll::Ready(());
+        }
+        if !wakers.iter().any(|waker| waker.will_wake(cx.waker())) {
+            wakers.push(cx.waker().clone());
+        }
+        Poll::Pending
+    }
+}
@@ -0,0 +1,494 @@
+//! Completion of command-line arguments, including argument values computed by
+//! `#[cling(complete = ...)]` functions.
+use std::io::Write;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use clap::{Arg, ArgAction, Command, Parser};
+
+use crate::app::{Cling, Run, Settings};
+use crate::layer::Layer;
+use crate::params::{CollectedArgs, CompletionRequest, InsertState};
+
+/// Set by the completion scripts of `Cling::generate_completions()` when they
+/// run the binary to complete a command line, see [Completion::complete_env].
+pub(crate) const COMPLETE_ENV: &str = "CLING_COMPLETE";
+
+/// The words of the command line to complete, if the process was started by
+/// a completion script.
+pub(crate) fn env_words() -> Option<Vec<String>> {
+    std::env::var_os(COMPLETE_ENV)?;
+    Some(
+        std::env::args_os()
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .skip_while(|arg| arg != "--")
+            .skip(1)
+            .collect(),
+    )
+}
+
+/// Prints `candidates` one per line, as completion scripts expect, and exits.
+pub(crate) fn print_candidates_and_exit(candidates: Vec<String>) -> ! {
+    let mut stdout = crate::console::stdout();
+    for candidate in candidates {
+        let _ = writeln!(stdout, "{}", candidate);
+    }
+    std::process::exit(0);
+}
+
+/// Computes completion candidates for a partially typed command line of `T`.
+///
+/// Subcommands, flags, and argument values listed in `possible_values` are
+/// completed from the clap command. Values of fields marked with
+/// `#[cling(complete = "...")]` are completed by calling that function, which
+/// accepts the same arguments as handlers and returns [Candidates]
+/// (optionally wrapped in a `Result` and/or async).
+///
+/// To run a completion function, the words before the cursor are parsed into
+/// `T` along with the word under the cursor, and the arguments are collected
+/// as they would be when running the command. No handlers run in the process.
+/// If parsing fails (e.g. because a required argument or subcommand is still
+/// missing) or the function fails, the static candidates are used instead.
+///
+/// Example:
+/// ```rust
+/// use cling::prelude::*;
+///
+/// #[derive(Run, Parser, Debug, Clone)]
+/// #[command(name = "app")]
+/// pub struct App {
+///     #[command(subcommand)]
+///     pub cmd: Commands,
+/// }
+///
+/// #[derive(Run, Subcommand, Debug, Clone)]
+/// pub enum Commands {
+///     Open(Open),
+/// }
+///
+/// #[derive(Run, Collect, Args, Debug, Clone)]
+/// #[cling(run = "open")]
+/// pub struct Open {
+///     #[arg(long)]
+///     #[cling(complete = "project_names")]
+///     pub name: String,
+/// }
+///
+/// #[derive(Clone, Debug)]
+/// pub struct Db(Vec<String>);
+///
+/// fn project_names(State(db): State<Db>) -> Candidates {
+///     db.0.into()
+/// }
+///
+/// fn open(open: &Open) {
+///     println!("Opening {}", open.name);
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let db = Db(vec!["cling".to_owned(), "clap".to_owned()]);
+/// let candidates = Cling::<App>::completion()
+///     .with_state(db)
+///     .complete(["app", "open", "--name", "cl"])
+///     .await;
+/// assert_eq!(vec!["clap", "cling"], candidates);
+/// # }
+/// ```
+///
+/// [Candidates]: crate::Candidates
+pub struct Completion<T> {
+    settings: Settings,
+    states: Vec<InsertState>,
+    _parsed_type: PhantomData<fn() -> T>,
+}
+
+impl<T: Run + Parser> Cling<T> {
+    /// Creates a [Completion] that completes command lines of `T`.
+    pub fn completion() -> Completion<T> {
+        Completion::new(Settings::default(), Vec::new())
+    }
+}
+
+impl<T: Run + Parser> Completion<T> {
+    pub(crate) fn new(settings: Settings, states: Vec<InsertState>) -> Self {
+        Completion {
+            settings,
+            states,
+            _parsed_type: PhantomData,
+        }
+    }
+
+    /// Makes `state` available to completion functions via
+    /// [State](crate::State).
+    pub fn with_state<S>(mut self, state: S) -> Self
+    where
+        S: Clone + Send + Sync + std::fmt::Debug + 'static,
+    {
+        self.states.push(Arc::new(move |args: &mut CollectedArgs| {
+            args.insert_debug_state(state.clone())
+        }));
+        self
+    }
+
+    /// Wraps command dispatch and completion functions in `layer`. See
+    /// [Layer] for details.
+    pub fn layer(mut self, layer: impl Layer) -> Self {
+        self.settings.layers.push(Arc::new(layer));
+        self
+    }
+
+    /// Returns the sorted candidates for the last word of `words`. The first
+    /// word is the binary name, and the last word is the (possibly empty)
+    /// word under the cursor.
+    pub async fn complete<I, B>(&self, words: I) -> Vec<String>
+    where
+        I: IntoIterator<Item = B>,
+        B: Into<String>,
+    {
+        let mut states = CollectedArgs::new();
+        for insert_state in &self.states {
+            insert_state(&mut states);
+        }
+        self.complete_with(words.into_iter().map(Into::into).collect(), states)
+            .await
+    }
+
+    /// Like [Completion::complete], with `states` available to completion
+    /// functions.
+    pub(crate) async fn complete_with(
+        &self,
+        mut words: Vec<String>,
+        states: CollectedArgs,
+    ) -> Vec<String> {
+        if words.is_empty() {
+            return Vec::new();
+        }
+        let current = words.pop().unwrap_or_default();
+
+        let mut command = T::command();
+        // Building the command propagates global arguments to subcommands.
+        command.build();
+        let target = locate(&command, &words[1..], &current);
+
+        let mut candidates = target.static_candidates();
+        if let Some(arg) = target.value_of {
+            let request = CompletionRequest::new(
+                (!arg.is_global_set()).then_some(target.level),
+                arg.get_id().to_string(),
+            );
+            words.push(current.clone());
+            if let Some(dynamic) = self.run(words, request, states).await {
+                candidates = dynamic
+                    .into_iter()
+                    .map(|value| format!("{}{}", target.value_prefix, value))
+                    .chain(target.subcommand_names())
+                    .collect();
+            }
+        }
+        candidates.retain(|candidate| candidate.starts_with(&current));
+        candidates.sort();
+        candidates.dedup();
+        candidates
+    }
+
+    /// Answers the request of a completion script generated by
+    /// `Cling::generate_completions()` and exits, if the process was started
+    /// by one. Does nothing otherwise.
+    ///
+    /// The scripts run the binary with the `CLING_COMPLETE` environment
+    /// variable set, followed by `--` and the words of the command line up to
+    /// the cursor. The candidates are printed to stdout, one per line.
+    ///
+    /// Applications parsed by cling (e.g. with `Cling::parse()` or
+    /// `Cling::parse_and_run()`) answer these requests when they run, with
+    /// the layers and states of the run. Only applications that parse the
+    /// command line with clap themselves, e.g. `App::parse().into_cling()`,
+    /// need to call this before parsing.
+    pub async fn complete_env(&self) {
+        if let Some(words) = env_words() {
+            print_candidates_and_exit(self.complete(words).await);
+        }
+    }
+
+    /// Parses `words` and runs the completion function of the requested
+    /// argument, if any.
+    async fn run(
+        &self,
+        words: Vec<String>,
+        request: CompletionRequest,
+        mut states: CollectedArgs,
+    ) -> Option<Vec<String>> {
+        let parsed = match T::try_parse_from(words) {
+            | Ok(parsed) => parsed,
+            | Err(e) => {
+                tracing::debug!(
+                    target: "cling",
+                    "Cannot complete `{}`, parsing failed: {}",
+                    request.arg_id,
+                    e.kind()
+                );
+                return None;
+            }
+        };
+        states.start_completion(request);
+        let mut finished =
+            Cling::with_settings(parsed, self.settings.clone(), states)
+                .run()
+                .await;
+        if let Err(e) = finished.result_ref() {
+            tracing::debug!(target: "cling", "Completion failed: {}", e);
+            return None;
+        }
+        finished.collected_arguments_mut().take_candidates()
+    }
+}
+
+/// Where the word under the cursor is on the command line.
+pub(crate) struct Target<'c> {
+    /// The innermost command selected by the preceding words.
+    command: &'c Command,
+    /// Number of subcommands selected by the preceding words.
+    level: usize,
+    /// The argument whose value is under the cursor.
+    value_of: Option<&'c Arg>,
+    /// Text that precedes the value in the word under the cursor (e.g.
+    /// `--name=`).
+    value_prefix: String,
+    /// Whether the word under the cursor can be a subcommand.
+    expects_subcommand: bool,
+    /// Whether the word under the cursor can be a flag.
+    expects_flag: bool,
+}
+
+impl Target<'_> {
+    /// Candidates computed from the command, without running any completion
+    /// functions.
+    pub(crate) fn static_candidates(&self) -> Vec<String> {
+        let mut candidates: Vec<String> = self
+            .value_of
+            .into_iter()
+            .flat_map(|arg| arg.get_possible_values())
+            .filter(|value| !value.is_hide_set())
+            .map(|value| format!("{}{}", self.value_prefix, value.get_name()))
+            .chain(self.subcommand_names())
+            .collect();
+        if self.expects_flag {
+            candidates.extend(
+                self.command
+                    .get_arguments()
+                    .filter(|arg| !arg.is_hide_set())
+                    .filter_map(|arg| arg.get_long())
+                    .map(|long| format!("--{long}")),
+            );
+        }
+        candidates
+    }
+
+    fn subcommand_names(&self) -> Vec<String> {
+        if !self.expects_subcommand {
+            return Vec::new();
+        }
+        self.command
+            .get_subcommands()
+            .filter(|subcommand| !subcommand.is_hide_set())
+            .map(|subcommand| subcommand.get_name().to_owned())
+            .collect()
+    }
+}
+
+/// Finds what the word under the cursor (`current`) completes, given the
+/// preceding `words` (without the binary name).
+pub(crate) fn locate<'c>(
+    command: &'c Command,
+    words: &[String],
+    current: &str,
+) -> Target<'c> {
+    let mut command = command;
+    let mut level = 0;
+    let mut pending_value: Option<&Arg> = None;
+    let mut positionals = 0;
+    let mut only_positionals = false;
+    for word in words {
+        if pending_value.take().is_some() {
+            continue;
+        }
+        if only_positionals {
+            positionals += 1;
+        } else if word == "--" {
+            only_positionals = true;
+        } else if let Some(long) = word.strip_prefix("--") {
+            if !long.contains('=') {
+                pending_value = find_long(command, long);
+            }
+        } else if let Some(shorts) = word.strip_prefix('-') {
+            pending_value = shorts
+                .chars()
+                .last()
+                .and_then(|short| {
+                    command
+                        .get_arguments()
+                        .find(|arg| arg.get_short() == Some(short))
+                })
+                .filter(|arg| takes_value(arg));
+        } else if let Some(subcommand) = command.find_subcommand(word) {
+            command = subcommand;
+            level += 1;
+            positionals = 0;
+        } else {
+            positionals += 1;
+        }
+    }
+
+    let mut target = Target {
+        command,
+        level,
+        value_of: pending_value,
+        value_prefix: String::new(),
+        expects_subcommand: false,
+        expects_flag: false,
+    };
+    if target.value_of.is_some() {
+        return target;
+    }
+    if !only_positionals && current.starts_with('-') {
+        if let Some((long, _)) =
+            current.strip_prefix("--").and_then(|c| c.split_once('='))
+        {
+            target.value_of = find_long(command, long);
+            target.value_prefix = format!("--{long}=");
+        } else {
+            target.expects_flag = true;
+        }
+        return target;
+    }
+    target.expects_subcommand = !only_positionals;
+    target.value_of = positional_at(command, positionals);
+    target
+}
+
+fn find_long<'c>(command: &'c Command, long: &str) -> Option<&'c Arg> {
+    command
+        .get_arguments()
+        .find(|arg| arg.get_long() == Some(long))
+        .filter(|arg| takes_value(arg))
+}
+
+/// Returns the positional argument that receives the positional value at
+/// `index`.
+fn positional_at(command: &Command, index: usize) -> Option<&Arg> {
+    let mut remaining = index;
+    for arg in command.get_positionals() {
+        let max_values = if matches!(arg.get_action(), ArgAction::Append) {
+            usize::MAX
+        } else {
+            arg.get_num_args().map_or(1, |range| range.max_values())
+        };
+        if remaining < max_values {
+            return Some(arg);
+        }
+        remaining -= max_values;
+    }
+    None
+}
+
+pub(crate) fn takes_value(arg: &Arg) -> bool {
+    arg.get_num_args()
+        .map_or(arg.get_action().takes_values(), |range| {
+            range.takes_values()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+
+    use super::*;
+
+    #[derive(Parser, Debug)]
+    #[command(name = "app")]
+    struct App {
+        #[arg(long, global = true)]
+        color: Option<Color>,
+        #[command(subcommand)]
+        cmd: Commands,
+    }
+
+    #[derive(Subcommand, Debug)]
+    enum Commands {
+        Copy {
+            #[arg(short, long)]
+            force: bool,
+            source: String,
+            destinations: Vec<String>,
+        },
+        #[command(subcommand)]
+        Remote(RemoteCommands),
+    }
+
+    #[derive(Subcommand, Debug)]
+    enum RemoteCommands {
+        Add { name: String },
+    }
+
+    #[derive(ValueEnum, Clone, Debug)]
+    enum Color {
+        Red,
+        Green,
+    }
+
+    fn describe(words: &[&str], current: &str) -> (usize, Option<String>) {
+        let mut command = App::command();
+        command.build();
+        let words: Vec<String> = words.iter().map(|s| s.to_string()).collect();
+        let target = locate(&command, &words, current);
+        (
+            target.level,
+            target.value_of.map(|arg| arg.get_id().to_string()),
+        )
+    }
+
+    #[test]
+    fn test_locate() {
+        assert_eq!((0, None), describe(&[], ""));
+        assert_eq!((0, Some("color".to_owned())), describe(&["--color"], ""));
+        assert_eq!((1, Some("source".to_owned())), describe(&["copy"], "s"));
+        assert_eq!(
+            (1, Some("destinations".to_owned())),
+            describe(&["copy", "-f", "a", "b"], "")
+        );
+        assert_eq!(
+            (1, Some("color".to_owned())),
+            describe(&["copy"], "--color=r")
+        );
+        assert_eq!((1, None), describe(&["copy"], "--f"));
+        assert_eq!(
+            (2, Some("name".to_owned())),
+            describe(&["remote", "add"], "")
+        );
+    }
+
+    #[test]
+    fn test_static_candidates() {
+        let mut command = App::command();
+        command.build();
+        let candidates = |words: &[&str], current: &str| {
+            let words: Vec<String> =
+                words.iter().map(|s| s.to_string()).collect();
+            let mut candidates =
+                locate(&command, &words, current).static_candidates();
+            candidates.sort();
+            candidates
+        };
+        assert_eq!(vec!["copy", "help", "remote"], candidates(&[], ""));
+        assert_eq!(vec!["green", "red"], candidates(&["--color"], ""));
+        assert_eq!(
+            vec!["--color=green", "--color=red"],
+            candidates(&["copy"], "--color=")
+        );
+        assert_eq!(
+            vec!["--color", "--force", "--help"],
+            candidates(&["copy"], "-")
+        );
+    }
+}
@@ -0,0 +1,132 @@
+//! Shell completion scripts generated from the command tree.
+use std::io::Write;
+
+use clap::{Args, Parser};
+pub use clap_complete::Shell;
+use indoc::formatdoc;
+
+use crate::app::{Cling, Run};
+use crate::complete::COMPLETE_ENV;
+use crate::params::Collect;
+
+impl<T: Run + Parser> Cling<T> {
+    /// Writes the completion script of `T` for `shell` to `writer`.
+    ///
+    /// The script completes the binary name of `T` (or the command name if the
+    /// binary name is not set). The Bash, Zsh and Fish scripts run the binary
+    /// to compute the candidates, which includes the values of fields marked
+    /// with `#[cling(complete = "...")]`, see
+    /// [Completion::complete_env](crate::Completion::complete_env). Scripts
+    /// for other shells complete from the command tree only.
+    ///
+    /// Example:
+    /// ```rust
+    /// use cling::prelude::*;
+    /// use cling::Shell;
+    ///
+    /// #[derive(Run, Parser, Debug, Clone)]
+    /// #[command(name = "app")]
+    /// #[cling(run = "run")]
+    /// pub struct App {
+    ///     #[arg(long)]
+    ///     pub verbose: bool,
+    /// }
+    ///
+    /// fn run() {}
+    ///
+    /// let mut script = Vec::new();
+    /// Cling::<App>::generate_completions(Shell::Bash, &mut script);
+    /// assert!(String::from_utf8(script).unwrap().contains("CLING_COMPLETE"));
+    /// ```
+    pub fn generate_completions(shell: Shell, writer: &mut dyn Write) {
+        let mut command = T::command();
+        let bin_name = command
+            .get_bin_name()
+            .unwrap_or(command.get_name())
+            .to_owned();
+        // Shell function names can't contain all characters of binary names.
+        let function: String = bin_name
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect();
+        let script = match shell {
+            | Shell::Bash => {
+                formatdoc! {r#"
+                    _cling_{function}() {{
+                        local IFS=$'\n'
+                        COMPREPLY=($({COMPLETE_ENV}=bash "${{COMP_WORDS[0]}}" -- "${{COMP_WORDS[@]:0:COMP_CWORD+1}}" 2>/dev/null))
+                    }}
+                    complete -o default -F _cling_{function} {bin_name}
+                "#}
+            }
+            | Shell::Zsh => {
+                formatdoc! {r#"
+                    #compdef {bin_name}
+
+                    _cling_{function}() {{
+                        local -a candidates
+                        candidates=("${{(@f)$({COMPLETE_ENV}=zsh "${{words[1]}}" -- "${{(@)words[1,CURRENT]}}" 2>/dev/null)}}")
+                        candidates=(${{candidates:#}})
+                        compadd -a candidates
+                    }}
+
+                    compdef _cling_{function} {bin_name}
+                "#}
+            }
+            | Shell::Fish => {
+                formatdoc! {r#"
+                    complete -c {bin_name} -f -a '(env {COMPLETE_ENV}=fish (commandline -opc)[1] -- (commandline -opc) (commandline -ct) 2>/dev/null)'
+                "#}
+            }
+            | _ => {
+                clap_complete::generate(shell, &mut command, bin_name, writer);
+                return;
+            }
+        };
+        let _ = writer.write_all(script.as_bytes());
+    }
+}
+
+/// Arguments of a `completions` subcommand that prints the completion script
+/// for a shell. Pair it with [print_completions] to add the subcommand to an
+/// application.
+///
+/// Example:
+/// ```rust
+/// use cling::prelude::*;
+/// use cling::ShellCompletions;
+///
+/// #[derive(Run, Parser, Debug, Clone)]
+/// pub struct App {
+///     #[command(subcommand)]
+///     pub cmd: Commands,
+/// }
+///
+/// #[derive(Run, Subcommand, Debug, Clone)]
+/// pub enum Commands {
+///     /// Print the completion script for a shell
+///     #[command(hide = true)]
+///     Completions(Completions),
+/// }
+///
+/// #[derive(Run, Collect, Args, Debug, Clone)]
+/// #[cling(run = "cling::print_completions::<App>")]
+/// pub struct Completions {
+///     #[command(flatten)]
+///     pub completions: ShellCompletions,
+/// }
+/// ```
+#[derive(Args, Debug, Clone)]
+pub struct ShellCompletions {
+    /// The shell to generate the completion script for
+    #[arg(value_enum)]
+    pub shell: Shell,
+}
+
+impl Collect for ShellCompletions {}
+
+/// Handler that prints the completion script of `T` to stdout for the shell
+/// selected in [ShellCompletions].
+pub fn print_completions<T: Run + Parser>(args: &ShellCompletions) {
+    Cling::<T>::generate_completions(args.shell, &mut crate::console::stdout());
+}
@@ -0,0 +1,151 @@
+//! Standard streams of the command, which tests can capture or provide.
+use std::cell::RefCell;
+use std::future::Future;
+use std::io::{self, Read, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::scoped::{self, Scoped};
+
+thread_local! {
+    static CURRENT_CAPTURE: RefCell<Option<Arc<Capture>>> =
+        const { RefCell::new(None) };
+}
+
+/// A handle to the standard output of the command. Returned by [stdout()].
+#[derive(Debug)]
+pub struct Stdout {
+    _private: (),
+}
+
+/// A handle to the standard error of the command. Returned by [stderr()].
+#[derive(Debug)]
+pub struct Stderr {
+    _private: (),
+}
+
+/// A handle to the standard input of the command. Returned by [stdin()].
+#[derive(Debug)]
+pub struct Stdin {
+    _private: (),
+}
+
+/// The standard output of the command.
+///
+/// Writes go to [std::io::stdout] unless the command runs under a
+/// [TestRunner](crate::testing::TestRunner), which captures them. Handlers
+/// that write with `writeln!(cling::stdout(), ...)` instead of `println!` can
+/// be tested in-process.
+///
+/// Example:
+/// ```rust
+/// use std::io::Write;
+///
+/// fn greet(name: &str) -> std::io::Result<()> {
+///     writeln!(cling::stdout(), "Hello, {}!", name)
+/// }
+/// ```
+pub fn stdout() -> Stdout {
+    Stdout { _private: () }
+}
+
+/// The standard error of the command.
+///
+/// Like [stdout()], writes go to [std::io::stderr] unless captured by a
+/// [TestRunner](crate::testing::TestRunner). Errors printed by cling are
+/// written here.
+pub fn stderr() -> Stderr {
+    Stderr { _private: () }
+}
+
+/// The standard input of the command.
+///
+/// Reads from [std::io::stdin] unless the command runs under a
+/// [TestRunner](crate::testing::TestRunner), which provides the input of its
+/// [Sandbox](crate::testing::Sandbox), or no input at all.
+pub fn stdin() -> Stdin {
+    Stdin { _private: () }
+}
+
+impl Read for Stdin {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match Capture::current() {
+            | Some(capture) => capture.stdin.lock().unwrap().read(buf),
+            | None => io::stdin().read(buf),
+        }
+    }
+}
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match Capture::current() {
+            | Some(capture) => capture.stdout.lock().unwrap().write(buf),
+            | None => io::stdout().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match Capture::current() {
+            | Some(_) => Ok(()),
+            | None => io::stdout().flush(),
+        }
+    }
+}
+
+impl Write for Stderr {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match Capture::current() {
+            | Some(capture) => capture.stderr.lock().unwrap().write(buf),
+            | None => io::stderr().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match Capture::current() {
+            | Some(_) => Ok(()),
+            | None => io::stderr().flush(),
+        }
+    }
+}
+
+/// Buffers that [stdin()] reads from and [stdout()] and [stderr()] write to
+/// while a future runs under [Capture::scope].
+#[derive(Debug, Default)]
+pub(crate) struct Capture {
+    stdin: Mutex<io::Cursor<Vec<u8>>>,
+    stdout: Mutex<Vec<u8>>,
+    stderr: Mutex<Vec<u8>>,
+}
+
+impl Capture {
+    pub(crate) fn with_stdin(input: Vec<u8>) -> Self {
+        Self {
+            stdin: Mutex::new(io::Cursor::new(input)),
+            ..Default::default()
+        }
+    }
+
+    /// Whether the current thread is polling a captured future.
+    pub(crate) fn is_active() -> bool {
+        Self::current().is_some()
+    }
+
+    fn current() -> Option<Arc<Capture>> {
+        scoped::current(&CURRENT_CAPTURE)
+    }
+
+    /// Captures the output of `future` every time it is polled.
+    pub(crate) fn scope<F: Future>(
+        self: &Arc<Self>,
+        future: F,
+    ) -> Scoped<Self, F> {
+        scoped::scope(&CURRENT_CAPTURE, self.clone(), future)
+    }
+
+    pub(crate) fn stdout(&self) -> String {
+        String::from_utf8_lossy(&self.stdout.lock().unwrap()).into_owned()
+    }
+
+    pub(crate) fn stderr(&self) -> String {
+        String::from_utf8_lossy(&self.stderr.lock().unwrap()).into_owned()
+    }
+}
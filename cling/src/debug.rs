@@ -0,0 +1,33 @@
+//! Prints the collected values before each handler, enabled with
+//! `Cling::debug_collected()` or `CLING_DEBUG=collected`.
+use std::io::Write;
+use std::sync::Arc;
+
+use crate::error::CliError;
+use crate::layer::{BoxFuture, Layer, Next};
+
+/// A built-in [Layer] that prints the type and `Debug` representation of
+/// every collected value to stderr before a handler runs.
+pub(crate) struct PrintCollected;
+
+impl Layer for PrintCollected {
+    fn call<'a>(
+        self: Arc<Self>,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<(), CliError>> {
+        let invocation = next.invocation();
+        if let Some(handler) = invocation.handler_name() {
+            let mut out = format!(
+                "Collected before {} `{}` of `{}`:\n",
+                invocation.kind(),
+                handler,
+                invocation.type_name(),
+            );
+            for (type_name, value) in next.args().debug_values() {
+                out.push_str(&format!("  {} = {}\n", type_name, value));
+            }
+            let _ = crate::console::stderr().write_all(out.as_bytes());
+        }
+        next.run()
+    }
+}
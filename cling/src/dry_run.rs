@@ -0,0 +1,160 @@
+//! Dry runs, in which handlers describe their changes instead of making them.
+use std::convert::Infallible;
+use std::future::Future;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use clap::Args;
+
+use crate::params::{Collect, CollectedArgs, FromCollected};
+
+/// A global `--dry-run` flag that enables the [DryRun] mode of the command.
+///
+/// Flatten it into the top-level command, then accept [DryRun] in handlers
+/// that make changes.
+#[derive(Args, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DryRunArgs {
+    /// Print the changes the command would make without making them
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+}
+
+impl Collect for DryRunArgs {}
+
+/// Extractor for the dry-run mode of the running command.
+///
+/// Dry-run is enabled by the `--dry-run` flag of [DryRunArgs] or by
+/// `Cling::dry_run()`. Handlers wrap their changes in a [Mutation] which runs
+/// the change normally, or records a description of it in dry-run mode. The
+/// recorded descriptions are printed as a plan when the finished command is
+/// reported, e.g. when returning [ClingFinished](crate::ClingFinished) from
+/// `main()`.
+///
+/// Example:
+/// ```rust
+/// use cling::prelude::*;
+///
+/// #[derive(Run, Parser, Debug, Clone)]
+/// #[cling(run = "delete")]
+/// pub struct App {
+///     #[command(flatten)]
+///     pub dry_run: DryRunArgs,
+/// }
+///
+/// fn delete(dry_run: DryRun) -> Result<(), CliError> {
+///     for project in ["a", "b"] {
+///         dry_run
+///             .mutation(format!("Delete project {}", project))
+///             .run(|| println!("Deleting {}", project));
+///     }
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DryRun {
+    enabled: bool,
+    plan: Arc<Mutex<Vec<String>>>,
+}
+
+impl DryRun {
+    /// Creates a dry-run mode with an empty plan.
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            plan: Default::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// A change described by `description` that only happens outside of
+    /// dry-run mode.
+    pub fn mutation(&self, description: impl Into<String>) -> Mutation {
+        Mutation {
+            dry_run: self.clone(),
+            description: description.into(),
+        }
+    }
+
+    /// Descriptions of the changes that were skipped, in order.
+    pub fn plan(&self) -> Vec<String> {
+        self.plan.lock().unwrap().clone()
+    }
+
+    /// Enables dry-run if `args` include a `--dry-run` flag that is set.
+    pub(crate) fn resolve(&self, args: &CollectedArgs) -> Self {
+        let requested =
+            args.get::<DryRunArgs>().is_some_and(|args| args.dry_run);
+        Self {
+            enabled: self.enabled || requested,
+            plan: self.plan.clone(),
+        }
+    }
+
+    /// Prints the plan to stdout, if dry-run is enabled.
+    pub(crate) fn print_plan(&self) {
+        if !self.enabled {
+            return;
+        }
+        let plan = self.plan();
+        let mut stdout = crate::console::stdout();
+        // Output is best effort, e.g. stdout might be a closed pipe.
+        if plan.is_empty() {
+            let _ = writeln!(stdout, "Dry run: nothing was changed.");
+            return;
+        }
+        let _ =
+            writeln!(stdout, "Dry run: nothing was changed. Planned changes:");
+        for description in plan {
+            let _ = writeln!(stdout, "  - {}", description);
+        }
+    }
+}
+
+impl<'a> FromCollected<'a> for DryRun {
+    type Rejection = Infallible;
+
+    fn from_collected(args: &'a CollectedArgs) -> Result<Self, Infallible> {
+        Ok(args.dry_run())
+    }
+}
+
+/// A change that is made outside of dry-run mode, or added to the plan in
+/// dry-run mode. Created with [DryRun::mutation].
+#[must_use = "mutations do nothing unless run"]
+#[derive(Debug)]
+pub struct Mutation {
+    dry_run: DryRun,
+    description: String,
+}
+
+impl Mutation {
+    /// Calls `f`, or records the mutation in dry-run mode and returns `None`.
+    pub fn run<T>(self, f: impl FnOnce() -> T) -> Option<T> {
+        if self.record() {
+            return None;
+        }
+        Some(f())
+    }
+
+    /// Awaits `future`, or records the mutation in dry-run mode and returns
+    /// `None` without polling the future.
+    pub async fn run_async<F: Future>(self, future: F) -> Option<F::Output> {
+        if self.record() {
+            return None;
+        }
+        Some(future.await)
+    }
+
+    /// Records the mutation if dry-run is enabled.
+    fn record(self) -> bool {
+        if !self.dry_run.enabled {
+            return false;
+        }
+        ::tracing::debug!(target: "cling", "Dry run: {}", self.description);
+        self.dry_run.plan.lock().unwrap().push(self.description);
+        true
+    }
+}
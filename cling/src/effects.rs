@@ -1,19 +1,105 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::sync::Arc;
+
+use crate::anymap::DebugFn;
 use crate::params::CollectedArgs;
 use crate::prelude::CliError;
+use crate::scoped::{self, Scoped};
 use crate::State;
 
 // Internal struct, not meant for public use.
 pub struct _Sync;
 // Internal struct, not meant for public use.
 pub struct _Async;
+// Internal struct, not meant for public use.
+#[cfg(feature = "output")]
+pub struct _Stream;
+
+thread_local! {
+    static CURRENT_CONTEXT: RefCell<Option<Arc<EffectContext>>> =
+        const { RefCell::new(None) };
+}
+
+/// Context that [IntoEffect::into_effect] can access while it runs, captured
+/// from the collected arguments before the handler is called.
+///
+/// `into_effect` doesn't have access to the collected arguments since the
+/// handler might still borrow them, so the context is set for the current
+/// thread every time the future is polled.
+#[doc(hidden)]
+#[derive(Debug, Default)]
+pub struct EffectContext {
+    #[cfg(feature = "output")]
+    pub(crate) output: crate::output::OutputArgs,
+}
+
+impl EffectContext {
+    #[allow(unused_variables)]
+    pub fn capture(args: &CollectedArgs) -> Self {
+        Self {
+            #[cfg(feature = "output")]
+            output: crate::output::output_args(args),
+        }
+    }
+
+    /// Makes the context current while `future` is polled.
+    pub fn scope<F: Future>(self, future: F) -> Scoped<Self, F> {
+        scoped::scope(&CURRENT_CONTEXT, Arc::new(self), future)
+    }
+
+    /// Calls `f` with the context of the effect being produced, if any.
+    #[cfg(feature = "output")]
+    pub(crate) fn with_current<R>(f: impl FnOnce(Option<&Self>) -> R) -> R {
+        f(scoped::current(&CURRENT_CONTEXT).as_deref())
+    }
+}
 
 /// A type returned by handlers to set state for downstream handlers
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Ord, PartialOrd)]
 pub struct SetState<S: Clone + Send + Sync + 'static>(pub S);
 
+/// Completion candidates returned by `#[cling(complete = ...)]` functions.
+///
+/// Example:
+/// ```rust
+/// use cling::prelude::*;
+///
+/// fn profiles() -> Candidates {
+///     ["dev", "prod"].into_iter().collect()
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Candidates(pub Vec<String>);
+
+impl<S: Into<String>> FromIterator<S> for Candidates {
+    fn from_iter<I: IntoIterator<Item = S>>(iter: I) -> Self {
+        Candidates(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<S: Into<String>> From<Vec<S>> for Candidates {
+    fn from(values: Vec<S>) -> Self {
+        values.into_iter().collect()
+    }
+}
+
 /// Defines the handler effect behaviour
 pub trait HandlerEffect {
     fn apply_effect(self, args: &mut CollectedArgs);
+
+    /// Like [HandlerEffect::apply_effect], with the `Debug` formatter of the
+    /// state the effect sets, if any.
+    #[doc(hidden)]
+    fn apply_effect_with_debug(
+        self,
+        args: &mut CollectedArgs,
+        _debug: Option<DebugFn>,
+    ) where
+        Self: Sized,
+    {
+        self.apply_effect(args)
+    }
 }
 
 impl HandlerEffect for () {
@@ -25,7 +111,21 @@ where
     S: Clone + Send + Sync + 'static,
 {
     fn apply_effect(self, args: &mut CollectedArgs) {
-        args.insert(State(self.0), true)
+        args.insert_state(self.0)
+    }
+
+    fn apply_effect_with_debug(
+        self,
+        args: &mut CollectedArgs,
+        debug: Option<DebugFn>,
+    ) {
+        args.insert_state_with_debug(self.0, debug)
+    }
+}
+
+impl HandlerEffect for Candidates {
+    fn apply_effect(self, args: &mut CollectedArgs) {
+        args.add_candidates(self.0)
     }
 }
 
@@ -68,6 +168,14 @@ where
     }
 }
 
+impl IntoEffect<_Sync> for Candidates {
+    type Effect = Candidates;
+
+    async fn into_effect(self) -> Result<Self::Effect, CliError> {
+        Ok(self)
+    }
+}
+
 impl<E, F> IntoEffect<_Sync> for Result<F, E>
 where
     E: Into<CliError>,
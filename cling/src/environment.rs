@@ -0,0 +1,166 @@
+//! Environment variables and working directory of the running command, which
+//! tests can replace without changing those of the process.
+use std::convert::Infallible;
+use std::env::VarError;
+use std::ffi::{OsStr, OsString};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use clap::{Command, Parser};
+
+use crate::params::{CollectedArgs, FromCollected};
+
+/// Extractor for the environment variables and the working directory of the
+/// running command.
+///
+/// Outside of tests, this is the environment of the process. Commands run by
+/// a [TestRunner](crate::testing::TestRunner) in a
+/// [Sandbox](crate::testing::Sandbox) see the variables and the working
+/// directory of the sandbox instead, so handlers that read them through this
+/// extractor can be tested in parallel. `std::env` and relative paths passed
+/// to `std::fs` always use the process environment.
+///
+/// Example:
+/// ```rust
+/// use cling::prelude::*;
+///
+/// fn load_config(env: Environment) -> Result<String, CliError> {
+///     let profile = env.var("APP_PROFILE").unwrap_or("dev".to_owned());
+///     let config = std::fs::read_to_string(env.path("config.toml"))?;
+///     Ok(format!("{profile}: {config}"))
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Environment {
+    // `None` for the environment of the process.
+    overlay: Option<Arc<Overlay>>,
+}
+
+#[derive(Debug)]
+struct Overlay {
+    // `None` removes the variable. Later entries take precedence.
+    vars: Vec<(OsString, Option<OsString>)>,
+    current_dir: PathBuf,
+}
+
+impl Overlay {
+    /// The value of `key` if the overlay sets or removes it.
+    fn lookup(&self, key: &OsStr) -> Option<Option<&OsStr>> {
+        self.vars
+            .iter()
+            .rev()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_deref())
+    }
+}
+
+impl Environment {
+    /// Replaces the variables in `vars` and the working directory of the
+    /// process.
+    pub(crate) fn overlay(
+        vars: Vec<(OsString, Option<OsString>)>,
+        current_dir: PathBuf,
+    ) -> Self {
+        Self {
+            overlay: Some(Arc::new(Overlay { vars, current_dir })),
+        }
+    }
+
+    /// Like [std::env::var_os].
+    pub fn var_os(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        let key = key.as_ref();
+        match self
+            .overlay
+            .as_ref()
+            .and_then(|overlay| overlay.lookup(key))
+        {
+            | Some(value) => value.map(OsStr::to_owned),
+            | None => std::env::var_os(key),
+        }
+    }
+
+    /// Like [std::env::var].
+    pub fn var(&self, key: impl AsRef<OsStr>) -> Result<String, VarError> {
+        self.var_os(key)
+            .ok_or(VarError::NotPresent)?
+            .into_string()
+            .map_err(VarError::NotUnicode)
+    }
+
+    /// Like [std::env::current_dir].
+    pub fn current_dir(&self) -> io::Result<PathBuf> {
+        match &self.overlay {
+            | Some(overlay) => Ok(overlay.current_dir.clone()),
+            | None => std::env::current_dir(),
+        }
+    }
+
+    /// Resolves `path` relative to the working directory. Absolute paths are
+    /// returned as is.
+    pub fn path(&self, path: impl AsRef<Path>) -> PathBuf {
+        match &self.overlay {
+            | Some(overlay) => overlay.current_dir.join(path),
+            // Relative paths already resolve against the process' directory.
+            | None => path.as_ref().to_owned(),
+        }
+    }
+
+    /// Parses `args` like `T::try_parse_from()`, with the values of `env`
+    /// arguments read from this environment.
+    pub(crate) fn parse<T, I, B>(&self, args: I) -> Result<T, clap::Error>
+    where
+        T: Parser,
+        I: IntoIterator<Item = B>,
+        B: Into<OsString> + Clone,
+    {
+        let Some(overlay) = &self.overlay else {
+            return T::try_parse_from(args);
+        };
+        let mut command = with_env(T::command(), overlay);
+        let mut matches = command.try_get_matches_from_mut(args)?;
+        T::from_arg_matches_mut(&mut matches)
+            .map_err(|e| e.format(&mut command))
+    }
+}
+
+/// Replaces the variables `command` and its subcommands read from the process
+/// with those of `overlay`.
+fn with_env(command: Command, overlay: &Overlay) -> Command {
+    command
+        .mut_args(|arg| {
+            let Some(value) = arg.get_env().and_then(|key| overlay.lookup(key))
+            else {
+                return arg;
+            };
+            // `Arg::env` reads the variable from the process. Without it, a
+            // default value takes precedence over the argument's own default
+            // and satisfies a required argument just like the variable would.
+            let value = value.map(|value| {
+                match arg.get_value_delimiter() {
+                    | Some(delimiter) => {
+                        value
+                            .to_string_lossy()
+                            .split(delimiter)
+                            .map(OsString::from)
+                            .collect()
+                    }
+                    | None => vec![value.to_owned()],
+                }
+            });
+            let arg = arg.env(None::<&'static str>);
+            match value {
+                | Some(values) => arg.default_values(values).required(false),
+                | None => arg,
+            }
+        })
+        .mut_subcommands(|subcommand| with_env(subcommand, overlay))
+}
+
+impl<'a> FromCollected<'a> for Environment {
+    type Rejection = Infallible;
+
+    fn from_collected(args: &'a CollectedArgs) -> Result<Self, Infallible> {
+        Ok(args.environment())
+    }
+}
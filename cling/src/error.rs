@@ -3,8 +3,16 @@ use std::io::Write;
 
 use clap::CommandFactory;
 use itertools::{Itertools, Position};
-use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
+use termcolor::{
+    Color,
+    ColorChoice,
+    ColorSpec,
+    NoColor,
+    StandardStream,
+    WriteColor,
+};
 
+use crate::console::Capture;
 use crate::prelude::ClingFinished;
 use crate::Run;
 
@@ -25,6 +33,12 @@ pub enum CliError {
     FailedWithMessageAndCode(String, u8),
     ClapError(clap::Error),
     InputString,
+    /// The command was interrupted by a signal (e.g. Ctrl-C). Exits with code
+    /// 130.
+    Interrupted,
+    /// A handler didn't complete within its `#[cling(timeout = "...")]`.
+    /// Exits with code 124.
+    TimedOut(String, std::time::Duration),
     Other(anyhow::Error),
     OtherWithCode(anyhow::Error, u8),
 }
@@ -40,6 +54,14 @@ impl From<anyhow::Error> for CliError {
     }
 }
 
+/// Extractors that can't fail have [Infallible](std::convert::Infallible)
+/// rejections.
+impl From<std::convert::Infallible> for CliError {
+    fn from(value: std::convert::Infallible) -> Self {
+        match value {}
+    }
+}
+
 impl From<std::io::Error> for CliError {
     fn from(value: std::io::Error) -> Self {
         CliError::Other(value.into())
@@ -110,6 +132,12 @@ impl Display for CliError {
             | CliError::InputString => {
                 write!(f, "Input string cannot be parsed as UNIX shell command")
             }
+            | CliError::Interrupted => {
+                write!(f, "Interrupted")
+            }
+            | CliError::TimedOut(handler, timeout) => {
+                write!(f, "Handler `{}` timed out after {:?}", handler, timeout)
+            }
             #[allow(unused_variables)]
             | CliError::InvalidHandler(msg) => {
                 #[cfg(not(debug_assertions))]
@@ -138,42 +166,57 @@ impl std::fmt::Debug for CliError {
 
 impl CliError {
     /// Pretty print the error to stderr.
+    ///
+    /// Help and version "errors" from clap are printed to stdout.
     pub fn print(&self) -> std::io::Result<()> {
-        let mut stderr = StandardStream::stderr(ColorChoice::Auto);
+        if !Capture::is_active() {
+            return self
+                .print_to(&mut StandardStream::stderr(ColorChoice::Auto));
+        }
+        // Captured output is never colored.
+        if let CliError::ClapError(e) = self {
+            let rendered = e.render().to_string();
+            if e.use_stderr() {
+                return write!(crate::console::stderr(), "{}", rendered);
+            }
+            return write!(crate::console::stdout(), "{}", rendered);
+        }
+        self.print_to(&mut NoColor::new(crate::console::stderr()))
+    }
+
+    fn print_to(&self, stderr: &mut dyn WriteColor) -> std::io::Result<()> {
         match self {
             | CliError::ClapError(e) => {
                 // Clap handles colors
                 e.print()
             }
-            | CliError::Failed => {
-                print_formatted_error(&mut stderr, "Aborted!", "")
-            }
+            | CliError::Failed => print_formatted_error(stderr, "Aborted!", ""),
             | CliError::FailedWithMessage(e) => {
-                print_formatted_error(&mut stderr, "", e)
+                print_formatted_error(stderr, "", e)
             }
             | CliError::FailedWithMessageAndCode(e, _) => {
-                print_formatted_error(&mut stderr, "", e)
-            }
-            | CliError::Other(e) => {
-                print_anyhow_error(&mut stderr, "Error: ", e)
+                print_formatted_error(stderr, "", e)
             }
+            | CliError::Other(e) => print_anyhow_error(stderr, "Error: ", e),
             | CliError::OtherWithCode(e, _) => {
-                print_anyhow_error(&mut stderr, "Error: ", e)
+                print_anyhow_error(stderr, "Error: ", e)
             }
-            | e @ CliError::InputString => {
-                print_formatted_error(&mut stderr, "", &e.to_string())
+            | e @ (CliError::InputString
+            | CliError::Interrupted
+            | CliError::TimedOut(..)) => {
+                print_formatted_error(stderr, "", &e.to_string())
             }
             #[allow(unused_variables)]
             | CliError::InvalidHandler(msg) => {
                 #[cfg(not(debug_assertions))]
                 let r = print_formatted_error(
-                    &mut stderr,
+                    stderr,
                     "\n\n** Cling Handler Design Error **\n\n",
                     "Detailed error message available only in debug builds.",
                 );
                 #[cfg(debug_assertions)]
                 let r = print_formatted_error(
-                    &mut stderr,
+                    stderr,
                     "\n\n** Cling Handler Design Error **\n\n",
                     msg,
                 );
@@ -187,6 +230,10 @@ impl CliError {
         match self {
             | CliError::FailedWithMessageAndCode(_, code) => *code,
             | CliError::OtherWithCode(_, code) => *code,
+            // 128 + SIGINT, as shells report it.
+            | CliError::Interrupted => 130,
+            // Same as coreutils' `timeout`.
+            | CliError::TimedOut(..) => 124,
             // Clap uses i32 for exit codes, we cast to u8 but fail with 255 if
             // out of bound.
             | CliError::ClapError(e) => {
@@ -210,7 +257,7 @@ impl CliError {
 static_assertions::assert_impl_all!(CliError: Send, Sync);
 
 fn print_formatted_error(
-    f: &mut StandardStream,
+    f: &mut dyn WriteColor,
     heading: &str,
     msg: &str,
 ) -> std::io::Result<()> {
@@ -222,7 +269,7 @@ fn print_formatted_error(
 }
 
 fn print_anyhow_error(
-    f: &mut StandardStream,
+    f: &mut dyn WriteColor,
     heading: &str,
     err: &anyhow::Error,
 ) -> std::io::Result<()> {
@@ -237,7 +284,7 @@ fn print_anyhow_error(
             if position == Position::First {
                 let _ =
                     f.set_color(ColorSpec::new().set_fg(Some(Color::Magenta)));
-                let _ = writeln!(f, "");
+                let _ = writeln!(f);
                 let _ = writeln!(f, "Caused by:");
                 let _ = f.reset();
             }
@@ -0,0 +1,104 @@
+//! A minimal executor for applications that don't use an async runtime.
+use std::future::Future;
+use std::pin::pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+/// Wakes the thread blocked in [block_on].
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Polls `future` on the current thread until it completes, parking the
+/// thread while the future is pending.
+///
+/// Futures that depend on the reactor of a runtime (e.g. tokio's IO or
+/// timers) never complete here.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        // Wake-ups between the poll and parking are not lost, `unpark()`
+        // makes the next `park()` return immediately.
+        thread::park();
+    }
+}
+
+/// Runs the body of a `#[cling::main]` function, on a multi-threaded tokio
+/// runtime if the `tokio` feature is enabled, or with [block_on] otherwise.
+#[doc(hidden)]
+pub fn block_on_main<F: Future>(future: F) -> F::Output {
+    #[cfg(feature = "tokio")]
+    {
+        tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .build()
+            .expect("Failed building the tokio runtime")
+            .block_on(future)
+    }
+    #[cfg(not(feature = "tokio"))]
+    {
+        block_on(future)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+
+    use super::*;
+
+    /// Pending until another thread sets the flag and wakes the task.
+    struct WokenByThread {
+        done: Arc<AtomicBool>,
+        spawned: bool,
+    }
+
+    impl Future for WokenByThread {
+        type Output = &'static str;
+
+        fn poll(
+            mut self: std::pin::Pin<&mut Self>,
+            cx: &mut Context<'_>,
+        ) -> Poll<Self::Output> {
+            if self.done.load(Ordering::SeqCst) {
+                return Poll::Ready("done");
+            }
+            if !self.spawned {
+                self.spawned = true;
+                let done = self.done.clone();
+                let waker = cx.waker().clone();
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(10));
+                    done.store(true, Ordering::SeqCst);
+                    waker.wake();
+                });
+            }
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn test_block_on() {
+        assert_eq!(42, block_on(async { 42 }));
+        let future = WokenByThread {
+            done: Arc::default(),
+            spawned: false,
+        };
+        assert_eq!("done", block_on(future));
+    }
+}
@@ -1,4 +1,7 @@
-use crate::params::{CollectedArgs, HandlerParam};
+use std::sync::Arc;
+
+use crate::error::CliError;
+use crate::params::{CollectedArgs, FromCollected, NotCollected};
 
 /// Extractor for state in handlers
 ///
@@ -34,15 +37,16 @@ use crate::params::{CollectedArgs, HandlerParam};
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Ord, PartialOrd)]
 pub struct State<S: Clone + Send + Sync + 'static>(pub S);
 
-impl<'a, S> HandlerParam<'a> for State<S>
+impl<'a, S> FromCollected<'a> for State<S>
 where
     S: Send + Sync + Clone + 'static,
 {
-    fn extract_param(args: &'a CollectedArgs) -> Option<Self> {
-        let Some(state) = args.get::<State<S>>() else {
-            return None;
-        };
-        Some(State(state.0.clone()))
+    type Rejection = NotCollected;
+
+    fn from_collected(args: &'a CollectedArgs) -> Result<Self, NotCollected> {
+        args.get::<State<S>>()
+            .cloned()
+            .ok_or_else(|| NotCollected::new::<Self>(args))
     }
 }
 
@@ -50,11 +54,75 @@ where
 /// An extractor for fields annotated with `#[cling(collect)]`
 pub struct Collected<T>(pub T);
 
-impl<'a, T> HandlerParam<'a> for Collected<T>
+impl<'a, T> FromCollected<'a> for Collected<T>
 where
     T: Send + Sync + Clone + 'static,
 {
-    fn extract_param(args: &'a CollectedArgs) -> Option<Self> {
-        args.get::<Self>().cloned()
+    type Rejection = NotCollected;
+
+    fn from_collected(args: &'a CollectedArgs) -> Result<Self, NotCollected> {
+        args.get::<Self>()
+            .cloned()
+            .ok_or_else(|| NotCollected::new::<Self>(args))
+    }
+}
+
+/// The outcome of a command while its `#[cling(finally = ...)]` handler runs.
+/// Handlers extract it as `&Result<(), CliError>`.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct Outcome(Arc<Result<(), CliError>>);
+
+impl Outcome {
+    /// Makes `outcome` available to the finally handler.
+    pub fn provide(
+        args: &mut CollectedArgs,
+        outcome: Result<(), CliError>,
+    ) -> Arc<Result<(), CliError>> {
+        let outcome = Arc::new(outcome);
+        args.insert(Outcome(outcome.clone()), true);
+        outcome
+    }
+
+    /// Takes the outcome back once the finally handler ran.
+    pub fn take(
+        args: &mut CollectedArgs,
+        outcome: Arc<Result<(), CliError>>,
+    ) -> Result<(), CliError> {
+        args.remove::<Outcome>();
+        // Layers can't keep a reference to the outcome, but if one of them
+        // did, the error is rebuilt from its message and exit code.
+        Arc::try_unwrap(outcome).unwrap_or_else(|outcome| {
+            match &*outcome {
+                | Ok(()) => Ok(()),
+                | Err(e) => {
+                    Err(CliError::FailedWithMessageAndCode(
+                        e.to_string(),
+                        e.exit_code(),
+                    ))
+                }
+            }
+        })
+    }
+
+    /// The outcome as `T`, if `T` is `Result<(), CliError>`.
+    #[cfg(unstable)]
+    pub(crate) fn get_as<T: 'static>(args: &CollectedArgs) -> Option<&T> {
+        let Outcome(outcome) = args.get::<Outcome>()?;
+        (&**outcome as &dyn std::any::Any).downcast_ref()
+    }
+}
+
+// On nightly, the `Collect` impls for all clap types would overlap with this
+// impl. There, the outcome is extracted by the `&T` extractor of collectable
+// types instead, see `params.rs`.
+#[cfg(not(unstable))]
+impl<'a> FromCollected<'a> for &'a Result<(), CliError> {
+    type Rejection = NotCollected;
+
+    fn from_collected(args: &'a CollectedArgs) -> Result<Self, NotCollected> {
+        args.get::<Outcome>()
+            .map(|Outcome(outcome)| &**outcome)
+            .ok_or_else(|| NotCollected::new::<Self>(args))
     }
 }
@@ -1,9 +1,7 @@
 use std::any::type_name;
 
-use indoc::formatdoc;
-
 use crate::effects::HandlerEffect;
-use crate::params::{CollectedArgs, HandlerParam};
+use crate::params::{CollectedArgs, FromCollected};
 use crate::prelude::CliError;
 use crate::IntoEffect;
 
@@ -14,6 +12,9 @@ where
     Output: IntoEffect<Type, Effect = F>,
 {
     fn call(self, args: &'a mut CollectedArgs) -> Result<Output, CliError>;
+
+    /// Type names of the handler's parameters.
+    fn param_types(&self) -> Vec<&'static str>;
 }
 
 impl<'a, Type, F, Output, Effect> Handler<'a, Type, ((),), Output, Effect> for F
@@ -25,6 +26,10 @@ where
     fn call(self, _args: &'a mut CollectedArgs) -> Result<Output, CliError> {
         Ok(self())
     }
+
+    fn param_types(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
 }
 
 macro_rules! handler_impl {
@@ -36,32 +41,31 @@ macro_rules! handler_impl {
             F: FnOnce($($ty,)*) -> Output + Send,
             Output: IntoEffect<Type, Effect = Effect>,
             Effect: HandlerEffect,
-            $($ty: HandlerParam<'a> + Send),*
+            $($ty: FromCollected<'a> + Send),*
         {
             fn call(self, args: &'a mut CollectedArgs) -> Result<Output, CliError> {
                 let handler_name = type_name::<Self>();
 
                 $(
-                let Some($ty) = $ty::extract_param(args) else {
-                    let mut collected = args.collected_types();
-                    collected.sort();
-                    return Err(CliError::InvalidHandler(formatdoc!{"
-                        In `{handler_name}`: Type `{}` was not collected from input arguments. Possible reasons:
-                           - The type doesn't implement `Collect` (add #[derive(Collect)])
-                           - The struct field wasn't marked with `#[cling(collect)]`
-                           - The type is not present in any fields, enums, or structs leading to this command in the command hierarchy.
-                           - The type is defined with Option<T> or Vec<T> and you used T, or vice versa
-
-                           Those are the types that have been collected: {:#?}
-                           "
-                         ,
-                             type_name::<$ty>(),
-                             collected,
-                         }));
+                let $ty = match $ty::from_collected(args) {
+                    | Ok(param) => param,
+                    | Err(rejection) => {
+                        return Err(match rejection.into() {
+                            // Design errors point at the handler to fix.
+                            | CliError::InvalidHandler(msg) => {
+                                CliError::InvalidHandler(format!("In `{handler_name}`: {msg}"))
+                            }
+                            | e => e,
+                        });
+                    }
                 };
                 )*
                 Ok(self($($ty),*))
             }
+
+            fn param_types(&self) -> Vec<&'static str> {
+                vec![$(type_name::<$ty>()),*]
+            }
         }
     };
 }
@@ -86,6 +90,7 @@ handler_impl!(
 );
 
 /// Compile-time test case.
+#[allow(dead_code)]
 const _: () = {
     #[derive(Clone)]
     struct CommonOpts;
@@ -0,0 +1,351 @@
+//! Middleware layers that wrap command dispatch and handler calls.
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use tracing::Instrument;
+
+use crate::params::CollectedArgs;
+use crate::prelude::CliError;
+
+/// A boxed future, used by [Layer] implementations.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+type Inner<'a> = Box<
+    dyn FnOnce(&'a mut CollectedArgs) -> BoxFuture<'a, Result<(), CliError>>
+        + Send
+        + 'a,
+>;
+
+/// What kind of work is being wrapped by a [Layer].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "output",
+    derive(serde::Serialize),
+    serde(rename_all = "snake_case")
+)]
+#[non_exhaustive]
+pub enum InvocationKind {
+    /// Dispatching a type that derives [Run](crate::Run). This wraps the
+    /// whole sub-tree of the command, including all handlers that run
+    /// within it.
+    Command,
+    /// Running a handler attached with `#[cling(run = "...")]`.
+    Run,
+    /// Running a handler attached with `#[cling(after = "...")]`.
+    After,
+    /// Running a handler attached with `#[cling(finally = "...")]`.
+    Finally,
+    /// Running a completion function attached to a field with
+    /// `#[cling(complete = "...")]`.
+    Complete,
+}
+
+impl std::fmt::Display for InvocationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            | InvocationKind::Command => "command",
+            | InvocationKind::Run => "run",
+            | InvocationKind::After => "after",
+            | InvocationKind::Finally => "finally",
+            | InvocationKind::Complete => "complete",
+        };
+        f.write_str(kind)
+    }
+}
+
+/// Describes the unit of work a [Layer] is wrapping.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Invocation {
+    kind: InvocationKind,
+    type_name: &'static str,
+    handler: Option<&'static str>,
+    params: Vec<&'static str>,
+    level: usize,
+}
+
+impl Invocation {
+    #[doc(hidden)]
+    pub fn command(type_name: &'static str) -> Self {
+        Self {
+            kind: InvocationKind::Command,
+            type_name,
+            handler: None,
+            params: Vec::new(),
+            level: 0,
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn handler(
+        kind: InvocationKind,
+        handler: &'static str,
+        type_name: &'static str,
+        params: Vec<&'static str>,
+    ) -> Self {
+        Self {
+            kind,
+            type_name,
+            handler: Some(handler),
+            params,
+            level: 0,
+        }
+    }
+
+    pub fn kind(&self) -> InvocationKind {
+        self.kind
+    }
+
+    /// Name of the [Run](crate::Run) type (or `Enum::Variant` for unit
+    /// variants) this invocation belongs to.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Path of the handler function as written in the `#[cling(...)]`
+    /// attribute. This is `None` for [InvocationKind::Command].
+    pub fn handler_name(&self) -> Option<&'static str> {
+        self.handler
+    }
+
+    /// Type names of the handler's parameters, in order. This is empty for
+    /// [InvocationKind::Command].
+    pub fn param_types(&self) -> &[&'static str] {
+        &self.params
+    }
+
+    /// Depth of the command in the command tree. The top-level command is
+    /// at level 0, and every subcommand adds one level.
+    pub fn level(&self) -> usize {
+        self.level
+    }
+
+    pub fn is_handler(&self) -> bool {
+        self.handler.is_some()
+    }
+
+    /// A `tracing` span covering the invocation, including its layers.
+    fn span(&self, args: &CollectedArgs) -> tracing::Span {
+        if !has_tracing_subscriber() {
+            return tracing::Span::none();
+        }
+        let command_path = args.command_path();
+        match self.handler {
+            | Some(handler) => {
+                tracing::debug_span!(
+                    target: "cling",
+                    "handler",
+                    handler,
+                    kind = %self.kind,
+                    type_name = self.type_name,
+                    command = command_path.as_str(),
+                    level = self.level,
+                )
+            }
+            | None => {
+                tracing::debug_span!(
+                    target: "cling",
+                    "command",
+                    type_name = self.type_name,
+                    command = command_path.as_str(),
+                    level = self.level,
+                )
+            }
+        }
+    }
+}
+
+/// Whether a `tracing` subscriber is installed. Without one, the `log` feature
+/// of `tracing` would forward every span entry and exit to `log` loggers.
+pub(crate) fn has_tracing_subscriber() -> bool {
+    tracing::dispatcher::get_default(|dispatch| {
+        !dispatch.is::<tracing::subscriber::NoSubscriber>()
+    })
+}
+
+/// Middleware that wraps command dispatch and handler calls.
+///
+/// Layers are registered with [Cling::layer](crate::Cling::layer) and run in
+/// the order they were added, the first layer being the outermost. A layer
+/// receives a [Next] that gives access to the collected arguments and can
+/// be used to continue the chain. Layers can observe the result of the
+/// wrapped call, short-circuit by returning without calling [Next::run], or
+/// wrap the future (e.g. for timing).
+///
+/// Example:
+/// ```rust
+/// use std::sync::Arc;
+///
+/// use cling::prelude::*;
+/// use cling::{BoxFuture, Layer, Next};
+///
+/// struct Audit;
+///
+/// impl Layer for Audit {
+///     fn call<'a>(
+///         self: Arc<Self>,
+///         next: Next<'a>,
+///     ) -> BoxFuture<'a, Result<(), CliError>> {
+///         Box::pin(async move {
+///             let invocation = next.invocation().clone();
+///             let result = next.run().await;
+///             if let Some(handler) = invocation.handler_name() {
+///                 eprintln!("{} finished: ok={}", handler, result.is_ok());
+///             }
+///             result
+///         })
+///     }
+/// }
+/// ```
+pub trait Layer: Send + Sync + 'static {
+    fn call<'a>(
+        self: Arc<Self>,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<(), CliError>>;
+}
+
+/// The remainder of the middleware chain.
+pub struct Next<'a> {
+    args: &'a mut CollectedArgs,
+    invocation: Invocation,
+    layers: Arc<[Arc<dyn Layer>]>,
+    index: usize,
+    inner: Inner<'a>,
+}
+
+impl<'a> Next<'a> {
+    /// The command or handler wrapped by this chain.
+    pub fn invocation(&self) -> &Invocation {
+        &self.invocation
+    }
+
+    /// Arguments and state collected so far.
+    pub fn args(&self) -> &CollectedArgs {
+        self.args
+    }
+
+    pub fn args_mut(&mut self) -> &mut CollectedArgs {
+        self.args
+    }
+
+    /// Runs the next layer, or the wrapped call if this is the last layer.
+    pub fn run(mut self) -> BoxFuture<'a, Result<(), CliError>> {
+        match self.layers.get(self.index).cloned() {
+            | Some(layer) => {
+                self.index += 1;
+                layer.call(self)
+            }
+            | None => (self.inner)(self.args),
+        }
+    }
+}
+
+/// Creates a [Layer] from an async function.
+///
+/// Example:
+/// ```rust
+/// use cling::prelude::*;
+/// use cling::Next;
+///
+/// async fn timing(next: Next<'_>) -> Result<(), CliError> {
+///     let start = std::time::Instant::now();
+///     let invocation = next.invocation().clone();
+///     let result = next.run().await;
+///     eprintln!("{} took {:?}", invocation.type_name(), start.elapsed());
+///     result
+/// }
+///
+/// let layer = cling::from_fn(timing);
+/// ```
+pub fn from_fn<F>(f: F) -> FromFn<F>
+where
+    F: for<'a> LayerFn<'a> + Send + Sync + 'static,
+{
+    FromFn(f)
+}
+
+/// A [Layer] created with [from_fn].
+pub struct FromFn<F>(F);
+
+impl<F> Layer for FromFn<F>
+where
+    F: for<'a> LayerFn<'a> + Send + Sync + 'static,
+{
+    fn call<'a>(
+        self: Arc<Self>,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<(), CliError>> {
+        Box::pin((self.0)(next))
+    }
+}
+
+/// Async functions that accept a [Next] and can be used with [from_fn].
+pub trait LayerFn<'a>: Fn(Next<'a>) -> <Self as LayerFn<'a>>::Future {
+    type Future: Future<Output = Result<(), CliError>> + Send + 'a;
+}
+
+impl<'a, F, Fut> LayerFn<'a> for F
+where
+    F: Fn(Next<'a>) -> Fut,
+    Fut: Future<Output = Result<(), CliError>> + Send + 'a,
+{
+    type Future = Fut;
+}
+
+/// Runs `inner` through the layers registered in `args`. Used by
+/// cling_derive.
+#[doc(hidden)]
+pub fn invoke<'a, F>(
+    args: &'a mut CollectedArgs,
+    mut invocation: Invocation,
+    inner: F,
+) -> BoxFuture<'a, Result<(), CliError>>
+where
+    F: FnOnce(&'a mut CollectedArgs) -> BoxFuture<'a, Result<(), CliError>>
+        + Send
+        + 'a,
+{
+    invocation.level = args.level();
+    let skipped = match invocation.kind {
+        | InvocationKind::Command | InvocationKind::Complete => false,
+        // Only the completion functions run while completing.
+        | _ if args.is_completing() => true,
+        | InvocationKind::Run => args.is_resumed_at(invocation.level),
+        | _ => false,
+    };
+    if skipped {
+        return Box::pin(async { Ok(()) });
+    }
+    // While planning, handlers are recorded instead of run and layers are
+    // bypassed.
+    if args.is_planning() {
+        if invocation.is_handler() {
+            args.plan_step(&invocation);
+            return Box::pin(async { Ok(()) });
+        }
+        return inner(args);
+    }
+    #[cfg(feature = "logging")]
+    crate::logging::init(args);
+    // `--timings` is collected with the arguments of the command that
+    // defines it, before its handlers run.
+    if invocation.is_handler() && args.is_timed() {
+        args.record_execution();
+    }
+    let inner: Inner<'a> = Box::new(inner);
+    let span = invocation.span(args);
+    let future = match args.layers() {
+        | Some(layers) => {
+            Next {
+                args,
+                invocation,
+                layers,
+                index: 0,
+                inner,
+            }
+            .run()
+        }
+        | None => inner(args),
+    };
+    Box::pin(future.instrument(span))
+}
@@ -5,13 +5,47 @@
 
 mod anymap;
 mod app;
+mod cancellation;
+#[cfg(feature = "completions")]
+mod complete;
+#[cfg(feature = "completions")]
+mod completions;
+mod console;
+mod debug;
+mod dry_run;
 mod effects;
+mod environment;
 mod error;
+mod executor;
 mod extractors;
 mod handler;
+mod layer;
+#[cfg(feature = "logging")]
+mod logging;
+#[cfg(feature = "manpages")]
+mod manpages;
+#[cfg(feature = "markdown")]
+mod markdown;
+mod metadata;
+#[cfg(feature = "output")]
+mod output;
 mod params;
+mod plan;
+#[cfg(feature = "repl")]
+mod repl;
+mod report;
+mod scoped;
+#[cfg(feature = "signals")]
+mod signals;
+#[cfg(feature = "output")]
+mod stream;
+#[cfg(feature = "output")]
+mod template;
+pub mod testing;
+mod timeout;
 
 pub use app::*;
+pub use cancellation::{Cancellation, Cancelled};
 #[cfg(feature = "derive")]
 /// Macro that adds a few assertions to help you investigate
 /// errors if the compiler is not happy about a handler signature.
@@ -44,6 +78,31 @@ pub use app::*;
 /// ```
 pub use cling_derive::cling_handler;
 #[cfg(feature = "derive")]
+/// Runs an `async fn main()` without depending on an async runtime.
+///
+/// The body runs on a multi-threaded tokio runtime if the `tokio` feature
+/// is enabled, and otherwise on a minimal built-in executor that is enough
+/// for applications whose handlers are sync or don't depend on a runtime.
+///
+/// ## Example:
+/// ```rust,no_run
+/// use cling::prelude::*;
+///
+/// #[derive(Run, Parser, Debug, Clone)]
+/// #[cling(run = "hello")]
+/// pub struct App;
+///
+/// fn hello() {
+///     println!("Hello!");
+/// }
+///
+/// #[cling::main]
+/// async fn main() -> ClingFinished<App> {
+///     Cling::parse_and_run().await
+/// }
+/// ```
+pub use cling_derive::main;
+#[cfg(feature = "derive")]
 /// Mark a clap struct/enum to be passed as a handler argument.
 ///
 /// **Note:** _Types that implement [Collect] must also be [Clone]._
@@ -94,9 +153,44 @@ pub use cling_derive::Collect;
 /// transparently. However, Cling only supports async on the top level,
 /// you'll need to pick an async runtime to execute the application.
 pub use cling_derive::Run;
-pub use effects::{IntoEffect, SetState};
+#[cfg(feature = "completions")]
+pub use complete::Completion;
+#[cfg(feature = "completions")]
+pub use completions::{print_completions, Shell, ShellCompletions};
+pub use console::{stderr, stdin, stdout, Stderr, Stdin, Stdout};
+pub use dry_run::{DryRun, DryRunArgs, Mutation};
+pub use effects::{Candidates, IntoEffect, SetState};
+pub use environment::Environment;
 pub use error::{CliError, CliErrorHandler};
 pub use extractors::{Collected, State};
+pub use layer::{
+    from_fn,
+    BoxFuture,
+    FromFn,
+    Invocation,
+    InvocationKind,
+    Layer,
+    LayerFn,
+    Next,
+};
+#[cfg(feature = "logging")]
+pub use logging::VerbosityArgs;
+pub use metadata::{HandlerMetadata, RunMetadata, SubcommandMetadata};
+#[cfg(feature = "output")]
+pub use output::{Output, OutputArgs, OutputFormat};
+pub use params::{
+    CollectedArgs,
+    CollectedArgsBuilder,
+    FromCollected,
+    NotCollected,
+};
+pub use plan::{ExplainArgs, Plan, PlanStep};
+#[cfg(feature = "repl")]
+pub use repl::Repl;
+pub use report::{ExecutionReport, HandlerOutcome, HandlerRun, TimingsArgs};
+#[cfg(feature = "output")]
+pub use template::Template;
+pub use timeout::{ThreadTimer, Timer};
 
 #[doc(hidden)]
 /// Used by cling_derive
@@ -104,8 +198,15 @@ pub mod _private {
     pub use {static_assertions, tracing};
 
     pub use crate::effects::*;
+    pub use crate::executor::block_on_main;
+    pub use crate::extractors::Outcome;
     pub use crate::handler::*;
+    pub use crate::layer::{invoke, Invocation, InvocationKind};
+    pub use crate::metadata::RunMetadata;
+    #[cfg(feature = "output")]
+    pub use crate::output::Render;
     pub use crate::params::*;
+    pub use crate::timeout::timeout;
 }
 
 /// Prelude module that contains most imports you'll need
@@ -119,7 +220,13 @@ pub mod prelude {
     pub use cling_derive::{cling_handler, Collect, Run};
 
     pub use crate::app::*;
+    pub use crate::cancellation::Cancellation;
+    pub use crate::dry_run::{DryRun, DryRunArgs};
+    pub use crate::effects::Candidates;
+    pub use crate::environment::Environment;
     pub use crate::error::*;
     pub use crate::extractors::*;
     pub use crate::handler::Handler;
+    #[cfg(feature = "output")]
+    pub use crate::output::{Output, OutputArgs, OutputFormat};
 }
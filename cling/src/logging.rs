@@ -0,0 +1,141 @@
+//! Installs a `tracing` subscriber from the verbosity flags of the command.
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use clap::{ArgAction, Args};
+use clap_verbosity_flag::{
+    DebugLevel,
+    ErrorLevel,
+    InfoLevel,
+    LogLevel,
+    OffLevel,
+    TraceLevel,
+    Verbosity,
+    WarnLevel,
+};
+use tracing::level_filters::LevelFilter;
+
+use crate::extractors::Collected;
+use crate::layer::has_tracing_subscriber;
+use crate::params::{Collect, CollectedArgs};
+
+/// Set once a verbosity flag was collected, the global subscriber can only be
+/// installed once per process.
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// Global `-v/--verbose` and `-q/--quiet` flags that set the level of the
+/// `tracing` subscriber installed by cling.
+///
+/// Errors are logged by default, each `-v` adds a level (warn, info, debug,
+/// trace) and `-q` turns logging off.
+#[derive(Args, Debug, Clone, Default, PartialEq, Eq)]
+pub struct VerbosityArgs {
+    /// Increase logging verbosity
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+    /// Decrease logging verbosity
+    #[arg(
+        short,
+        long,
+        action = ArgAction::Count,
+        global = true,
+        conflicts_with = "verbose"
+    )]
+    pub quiet: u8,
+}
+
+impl Collect for VerbosityArgs {}
+
+impl VerbosityArgs {
+    /// The maximum level of the events that are logged.
+    pub fn level_filter(&self) -> LevelFilter {
+        if self.quiet > 0 {
+            return LevelFilter::OFF;
+        }
+        match self.verbose {
+            | 0 => LevelFilter::ERROR,
+            | 1 => LevelFilter::WARN,
+            | 2 => LevelFilter::INFO,
+            | 3 => LevelFilter::DEBUG,
+            | _ => LevelFilter::TRACE,
+        }
+    }
+}
+
+/// The level of the first verbosity flag collected in `args`, either
+/// [VerbosityArgs] or a `#[cling(collect)]` field of
+/// `clap_verbosity_flag::Verbosity`.
+fn collected_level(args: &CollectedArgs) -> Option<LevelFilter> {
+    fn verbosity<L: LogLevel + Send + Sync + 'static>(
+        args: &CollectedArgs,
+    ) -> Option<LevelFilter> {
+        args.get::<Collected<Verbosity<L>>>()
+            .map(|Collected(verbosity)| verbosity.tracing_level_filter())
+    }
+    args.get::<VerbosityArgs>()
+        .map(VerbosityArgs::level_filter)
+        .or_else(|| verbosity::<ErrorLevel>(args))
+        .or_else(|| verbosity::<WarnLevel>(args))
+        .or_else(|| verbosity::<InfoLevel>(args))
+        .or_else(|| verbosity::<DebugLevel>(args))
+        .or_else(|| verbosity::<TraceLevel>(args))
+        .or_else(|| verbosity::<OffLevel>(args))
+}
+
+/// Installs a global subscriber that logs to [stderr()](crate::stderr) at the
+/// verbosity level collected in `args`, unless the application installed its
+/// own.
+///
+/// Called before every command and handler runs, so the subscriber is
+/// installed as soon as the command that defines the flags has collected
+/// them, and records the spans of its handlers and subcommands. Does nothing
+/// until a verbosity flag is collected, and after the first attempt.
+pub(crate) fn init(args: &CollectedArgs) {
+    if INITIALIZED.load(Ordering::Relaxed) {
+        return;
+    }
+    let Some(level) = collected_level(args) else {
+        return;
+    };
+    if INITIALIZED.swap(true, Ordering::SeqCst) || has_tracing_subscriber() {
+        return;
+    }
+    let result = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(crate::console::stderr)
+        .without_time()
+        .try_init();
+    if let Err(e) = result {
+        tracing::log::debug!(
+            target: "cling",
+            "Not installing a tracing subscriber: {}",
+            e
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_collected_level() {
+        let args = CollectedArgs::new();
+        assert_eq!(None, collected_level(&args));
+
+        let args = CollectedArgs::builder()
+            .with(&VerbosityArgs {
+                verbose: 2,
+                quiet: 0,
+            })
+            .build();
+        assert_eq!(Some(LevelFilter::INFO), collected_level(&args));
+
+        let mut args = CollectedArgs::new();
+        args.insert(Collected(Verbosity::<WarnLevel>::new(0, 0)), true);
+        assert_eq!(Some(LevelFilter::WARN), collected_level(&args));
+
+        let mut args = CollectedArgs::new();
+        args.insert(Collected(Verbosity::<ErrorLevel>::new(0, 1)), true);
+        assert_eq!(Some(LevelFilter::OFF), collected_level(&args));
+    }
+}
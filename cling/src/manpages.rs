@@ -0,0 +1,59 @@
+//! Man pages generated from the command tree.
+use std::path::{Path, PathBuf};
+
+use clap::{Command, Parser};
+
+use crate::app::{Cling, Run};
+
+impl<T: Run + Parser> Cling<T> {
+    /// Writes a roff man page for `T` and each of its subcommands into `dir`
+    /// and returns the paths of the written pages.
+    ///
+    /// Pages are named after the command path, e.g. `app.1`, `app-projects.1`
+    /// and `app-projects-list.1`. Hidden subcommands and clap's generated
+    /// `help` subcommand are skipped.
+    ///
+    /// Example:
+    /// ```rust,no_run
+    /// use cling::prelude::*;
+    ///
+    /// #[derive(Run, Parser, Debug, Clone)]
+    /// #[command(name = "app")]
+    /// #[cling(run = "run")]
+    /// pub struct App {}
+    ///
+    /// fn run() {}
+    ///
+    /// let pages = Cling::<App>::render_manpages("target/man").unwrap();
+    /// assert_eq!("app.1", pages[0].file_name().unwrap());
+    /// ```
+    pub fn render_manpages(
+        dir: impl AsRef<Path>,
+    ) -> std::io::Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        std::fs::create_dir_all(dir)?;
+        let mut command = T::command().disable_help_subcommand(true);
+        // Building the command assigns page names to subcommands (e.g.
+        // `app-projects`) and propagates global arguments.
+        command.build();
+        let mut pages = Vec::new();
+        render_command(&command, dir, &mut pages)?;
+        Ok(pages)
+    }
+}
+
+fn render_command(
+    command: &Command,
+    dir: &Path,
+    pages: &mut Vec<PathBuf>,
+) -> std::io::Result<()> {
+    pages.push(clap_mangen::Man::new(command.clone()).generate_to(dir)?);
+
+    for subcommand in command.get_subcommands() {
+        if subcommand.is_hide_set() {
+            continue;
+        }
+        render_command(subcommand, dir, pages)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,202 @@
+//! Markdown reference documentation generated from the command tree.
+use std::fmt::Write;
+
+use clap::{Arg, Command, Parser};
+
+use crate::app::{Cling, Run};
+use crate::complete::takes_value;
+use crate::metadata::{short_type_name, RunMetadata};
+
+impl<T: Run + Parser> Cling<T> {
+    /// Renders a Markdown reference of `T` with one section per command path.
+    ///
+    /// Each section lists the command's arguments with their defaults and
+    /// environment variables, the handlers that run for the command along
+    /// with the types they extract, the types the command collects, and links
+    /// to its subcommands. Hidden commands and arguments are skipped.
+    ///
+    /// Example:
+    /// ```rust
+    /// use cling::prelude::*;
+    ///
+    /// #[derive(Run, Collect, Parser, Debug, Clone)]
+    /// #[command(name = "app")]
+    /// #[cling(run = "run")]
+    /// pub struct App {
+    ///     /// Turn debugging information on
+    ///     #[arg(long)]
+    ///     pub debug: bool,
+    /// }
+    ///
+    /// fn run(_app: &App) {}
+    ///
+    /// let reference = Cling::<App>::render_markdown();
+    /// assert!(reference.starts_with("## `app`"));
+    /// assert!(reference.contains("`run(&App)`"));
+    /// ```
+    pub fn render_markdown() -> String {
+        let mut command = T::command().disable_help_subcommand(true);
+        // Building the command sets the usage of subcommands and propagates
+        // global arguments.
+        command.build();
+        let mut out = String::new();
+        render_command(&command, Some(&T::metadata()), &[], &mut out);
+        out
+    }
+}
+
+fn render_command(
+    command: &Command,
+    metadata: Option<&RunMetadata>,
+    parents: &[&str],
+    out: &mut String,
+) {
+    let mut path = parents.to_vec();
+    path.push(command.get_name());
+    let _ = writeln!(out, "## `{}`\n", path.join(" "));
+    if let Some(about) =
+        command.get_long_about().or_else(|| command.get_about())
+    {
+        let _ = writeln!(out, "{}\n", about);
+    }
+    let mut command_for_usage = command.clone();
+    let usage = command_for_usage.render_usage().to_string();
+    let usage = usage.trim_start_matches("Usage: ");
+    let _ = writeln!(out, "**Usage:** `{}`\n", usage);
+
+    if let Some(metadata) = metadata {
+        render_metadata(metadata, out);
+    }
+
+    let (positionals, options): (Vec<&Arg>, Vec<&Arg>) = command
+        .get_arguments()
+        .filter(|arg| !arg.is_hide_set())
+        .partition(|arg| arg.is_positional());
+    render_arguments("Arguments", &positionals, out);
+    render_arguments("Options", &options, out);
+
+    let subcommands: Vec<&Command> = command
+        .get_subcommands()
+        .filter(|subcommand| !subcommand.is_hide_set())
+        .collect();
+    if !subcommands.is_empty() {
+        let _ = writeln!(out, "**Subcommands:**\n");
+        for subcommand in &subcommands {
+            let anchor =
+                format!("{}-{}", path.join("-"), subcommand.get_name());
+            let _ = write!(
+                out,
+                "- [`{}`](#{})",
+                subcommand.get_name(),
+                anchor.to_lowercase()
+            );
+            if let Some(about) = subcommand.get_about() {
+                let _ = write!(out, ": {}", about);
+            }
+            let _ = writeln!(out);
+        }
+        let _ = writeln!(out);
+    }
+
+    for subcommand in subcommands {
+        let metadata = metadata.and_then(|metadata| {
+            metadata.find_subcommand(subcommand.get_name())
+        });
+        render_command(subcommand, metadata, &path, out);
+    }
+}
+
+fn render_metadata(metadata: &RunMetadata, out: &mut String) {
+    if !metadata.handlers.is_empty() {
+        let _ = writeln!(out, "**Handlers:**\n");
+        for handler in &metadata.handlers {
+            let params: Vec<String> = handler
+                .params
+                .iter()
+                .map(|param| short_type_name(param))
+                .collect();
+            let _ = writeln!(
+                out,
+                "- {}: `{}({})`",
+                handler.kind,
+                handler.name,
+                params.join(", ")
+            );
+        }
+        let _ = writeln!(out);
+    }
+    if !metadata.collects.is_empty() {
+        let collects: Vec<String> = metadata
+            .collects
+            .iter()
+            .map(|type_name| format!("`{}`", short_type_name(type_name)))
+            .collect();
+        let _ = writeln!(out, "**Collects:** {}\n", collects.join(", "));
+    }
+}
+
+fn render_arguments(title: &str, args: &[&Arg], out: &mut String) {
+    if args.is_empty() {
+        return;
+    }
+    let _ = writeln!(out, "**{}:**\n", title);
+    let _ = writeln!(out, "| Name | Description | Default | Environment |");
+    let _ = writeln!(out, "|------|-------------|---------|-------------|");
+    for arg in args {
+        let description = arg
+            .get_long_help()
+            .or_else(|| arg.get_help())
+            .map(|help| help.to_string())
+            .unwrap_or_default();
+        let defaults: Vec<String> = arg
+            .get_default_values()
+            .iter()
+            .map(|value| format!("`{}`", value.to_string_lossy()))
+            .collect();
+        let env = arg
+            .get_env()
+            .map(|env| format!("`{}`", env.to_string_lossy()))
+            .unwrap_or_default();
+        let _ = writeln!(
+            out,
+            "| {} | {} | {} | {} |",
+            argument_name(arg),
+            escape_cell(&description),
+            defaults.join(", "),
+            env,
+        );
+    }
+    let _ = writeln!(out);
+}
+
+fn argument_name(arg: &Arg) -> String {
+    let value_name = arg
+        .get_value_names()
+        .and_then(|names| names.first())
+        .map(|name| name.to_string())
+        .unwrap_or_else(|| arg.get_id().as_str().to_uppercase());
+    if arg.is_positional() {
+        return format!("`<{}>`", value_name);
+    }
+    let mut names = Vec::new();
+    if let Some(short) = arg.get_short() {
+        names.push(format!("-{}", short));
+    }
+    if let Some(long) = arg.get_long() {
+        names.push(format!("--{}", long));
+    }
+    let value = if takes_value(arg) {
+        format!(" <{}>", value_name)
+    } else {
+        String::new()
+    };
+    names
+        .into_iter()
+        .map(|name| format!("`{}{}`", name, value))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', "<br>")
+}
@@ -0,0 +1,148 @@
+//! Static description of the handlers and collected types of a command tree,
+//! generated by `#[derive(Run)]`.
+use clap::Parser;
+
+use crate::app::{Cling, Run};
+use crate::layer::InvocationKind;
+
+/// Describes a type that derives [Run](crate::Run): the handlers it runs, the
+/// types it collects, and its subcommands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RunMetadata {
+    /// Name of the type as written in the source (e.g. `App`).
+    pub type_name: &'static str,
+    /// Handlers attached to this type, in the order they run.
+    pub handlers: Vec<HandlerMetadata>,
+    /// Types this command inserts into the collected arguments, which can be
+    /// extracted by handlers of this command and its subcommands.
+    pub collects: Vec<&'static str>,
+    /// Subcommands, if this is an enum or a struct with a subcommand field.
+    pub subcommands: Vec<SubcommandMetadata>,
+}
+
+/// Describes a handler attached with `#[cling(run/after/finally = ...)]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct HandlerMetadata {
+    pub kind: InvocationKind,
+    /// Path of the handler function as written in the attribute.
+    pub name: &'static str,
+    /// Types of the handler's parameters.
+    pub params: Vec<&'static str>,
+}
+
+/// Describes a subcommand of a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SubcommandMetadata {
+    /// Name of the subcommand on the command line.
+    pub name: String,
+    pub metadata: RunMetadata,
+}
+
+impl RunMetadata {
+    #[doc(hidden)]
+    pub fn new(type_name: &'static str) -> Self {
+        Self {
+            type_name,
+            handlers: Vec::new(),
+            collects: Vec::new(),
+            subcommands: Vec::new(),
+        }
+    }
+
+    #[doc(hidden)]
+    pub fn handler(
+        mut self,
+        kind: InvocationKind,
+        name: &'static str,
+        params: Vec<&'static str>,
+    ) -> Self {
+        self.handlers.push(HandlerMetadata { kind, name, params });
+        self
+    }
+
+    #[doc(hidden)]
+    pub fn collects(mut self, type_name: &'static str) -> Self {
+        self.collects.push(type_name);
+        self
+    }
+
+    #[doc(hidden)]
+    pub fn subcommand(
+        mut self,
+        name: impl Into<String>,
+        metadata: RunMetadata,
+    ) -> Self {
+        self.subcommands.push(SubcommandMetadata {
+            name: name.into(),
+            metadata,
+        });
+        self
+    }
+
+    /// Adds the subcommands of `other`, used for flattened subcommands and
+    /// subcommand fields of structs.
+    #[doc(hidden)]
+    pub fn subcommands_of(mut self, other: RunMetadata) -> Self {
+        self.subcommands.extend(other.subcommands);
+        self
+    }
+
+    /// Returns the metadata of the subcommand `name`.
+    pub fn find_subcommand(&self, name: &str) -> Option<&RunMetadata> {
+        self.subcommands
+            .iter()
+            .find(|subcommand| subcommand.name == name)
+            .map(|subcommand| &subcommand.metadata)
+    }
+}
+
+impl<T: Run + Parser> Cling<T> {
+    /// Returns the handlers and collected types of `T` and its subcommands.
+    pub fn metadata() -> RunMetadata {
+        T::metadata()
+    }
+}
+
+/// Strips module paths from a type name, e.g.
+/// `cling::extractors::State<app::Db>` becomes `State<Db>`.
+pub(crate) fn short_type_name(type_name: &str) -> String {
+    let mut out = String::with_capacity(type_name.len());
+    let mut segment_start = 0;
+    let mut chars = type_name.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == ':' && chars.peek() == Some(&':') {
+            chars.next();
+            out.truncate(segment_start);
+        } else {
+            out.push(c);
+            if !(c.is_alphanumeric() || c == '_') {
+                segment_start = out.len();
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_type_name() {
+        assert_eq!("App", short_type_name("app::App"));
+        assert_eq!(
+            "State<Db>",
+            short_type_name("cling::extractors::State<my_app::db::Db>")
+        );
+        assert_eq!(
+            "&Option<Vec<String>>",
+            short_type_name(
+                "&core::option::Option<alloc::vec::Vec<alloc::string::String>>"
+            )
+        );
+        assert_eq!("(u8, Foo)", short_type_name("(u8, a::Foo)"));
+    }
+}
@@ -0,0 +1,525 @@
+//! Rendering of structured handler output as text, JSON, YAML, tables, or
+//! CSV.
+use std::io::Write;
+
+use clap::{Args, ValueEnum};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::effects::{_Sync, HandlerEffect, IntoEffect};
+use crate::error::CliError;
+use crate::params::{Collect, CollectedArgs};
+use crate::template::{lookup, parse_path, Template};
+
+/// The format [Output] values are rendered in.
+#[derive(ValueEnum, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum OutputFormat {
+    /// Human readable text
+    #[default]
+    Text,
+    /// Pretty-printed JSON
+    Json,
+    /// YAML
+    Yaml,
+    /// Aligned columns, one row per item
+    Table,
+    /// Comma-separated values with a header row
+    Csv,
+}
+
+impl Collect for OutputFormat {}
+
+/// Global `--output`, `--format`, and `--fields` flags that control how
+/// [Output] values are rendered.
+///
+/// Flatten it into the top-level command to let users pick the format of
+/// every subcommand. Applications that already have a flag for the output
+/// format can collect an [OutputFormat] field instead.
+///
+/// `--format` renders every item (or the value, if it isn't an array) with a
+/// [Template], e.g. `--format '{{.name}}\t{{.id}}'`, and takes precedence over
+/// `--output`. `--fields name,id` keeps only the listed fields of objects, in
+/// that order, before rendering.
+///
+/// Example:
+/// ```rust
+/// use cling::prelude::*;
+///
+/// #[derive(Run, Parser, Debug, Clone)]
+/// pub struct App {
+///     #[command(flatten)]
+///     pub output: OutputArgs,
+///     #[command(subcommand)]
+///     pub cmd: Commands,
+/// }
+///
+/// #[derive(Run, Subcommand, Debug, Clone)]
+/// pub enum Commands {
+///     #[cling(run = "list")]
+///     List,
+/// }
+///
+/// fn list() -> Output<Vec<String>> {
+///     Output::new(vec!["a".to_owned(), "b".to_owned()])
+/// }
+/// ```
+#[derive(Args, Debug, Clone, Default, PartialEq, Eq)]
+pub struct OutputArgs {
+    /// Output format
+    #[arg(
+        long,
+        short = 'o',
+        global = true,
+        value_enum,
+        default_value_t = OutputFormat::Text
+    )]
+    pub output: OutputFormat,
+    /// Render each item with a template, e.g. '{{.name}}\t{{.id}}'
+    #[arg(long, global = true, value_name = "TEMPLATE")]
+    pub format: Option<Template>,
+    /// Only output these fields, e.g. 'name,owner.id'
+    #[arg(long, global = true, value_delimiter = ',', value_name = "FIELDS")]
+    pub fields: Vec<String>,
+}
+
+impl OutputArgs {
+    /// Whether the value is rendered as the default text, without a template
+    /// or field selection.
+    fn is_plain_text(&self) -> bool {
+        self.output == OutputFormat::Text
+            && self.format.is_none()
+            && self.fields.is_empty()
+    }
+}
+
+impl Collect for OutputArgs {}
+
+/// Structured data returned by a handler, rendered by cling in the
+/// [OutputFormat] selected by the user.
+///
+/// The format is taken from the collected [OutputArgs], or from a collected
+/// [OutputFormat] if the application defines its own flag. Without either,
+/// values are rendered as text.
+///
+/// Example:
+/// ```rust
+/// use cling::prelude::*;
+/// use serde::Serialize;
+///
+/// #[derive(Serialize)]
+/// struct Project {
+///     name: String,
+///     stars: u32,
+/// }
+///
+/// fn list_projects() -> Result<Output<Vec<Project>>, CliError> {
+///     let projects = vec![Project {
+///         name: "cling".to_owned(),
+///         stars: 100,
+///     }];
+///     Ok(Output::new(projects))
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Output<T> {
+    value: T,
+    text: Option<String>,
+}
+
+impl<T: Serialize> Output<T> {
+    pub fn new(value: T) -> Self {
+        Self { value, text: None }
+    }
+
+    /// Uses `text` instead of the default rendering of the value when the
+    /// output format is [OutputFormat::Text].
+    pub fn with_text(mut self, text: impl Into<String>) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn into_value(self) -> T {
+        self.value
+    }
+
+    /// Renders the value in `format`, as it would be printed to stdout.
+    pub fn render(&self, format: OutputFormat) -> Result<String, CliError> {
+        self.render_with(&OutputArgs {
+            output: format,
+            ..Default::default()
+        })
+    }
+
+    /// Renders the value according to `args`, as it would be printed to
+    /// stdout.
+    pub fn render_with(&self, args: &OutputArgs) -> Result<String, CliError> {
+        match &self.text {
+            | Some(text) if args.is_plain_text() => Ok(text.clone()),
+            | _ => Ok(render_with(&to_value(&self.value)?, args)),
+        }
+    }
+}
+
+/// The effect of returning [Output] from a handler.
+#[doc(hidden)]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Render {
+    value: Value,
+    text: Option<String>,
+}
+
+impl HandlerEffect for Render {
+    fn apply_effect(self, args: &mut CollectedArgs) {
+        let output_args = output_args(args);
+        let rendered = match self.text {
+            | Some(text) if output_args.is_plain_text() => text,
+            | _ => render_with(&self.value, &output_args),
+        };
+        let rendered = rendered.trim_end_matches('\n');
+        // Nothing to render, e.g. an empty list, doesn't print a blank line.
+        if rendered.is_empty() {
+            return;
+        }
+        // Output is best effort, e.g. stdout might be a closed pipe.
+        let _ = writeln!(crate::console::stdout(), "{}", rendered);
+    }
+}
+
+impl<T> IntoEffect<_Sync> for Output<T>
+where
+    T: Serialize + Send,
+{
+    type Effect = Render;
+
+    async fn into_effect(self) -> Result<Self::Effect, CliError> {
+        Ok(Render {
+            value: to_value(&self.value)?,
+            text: self.text,
+        })
+    }
+}
+
+/// The output flags selected on the command line.
+pub(crate) fn output_args(args: &CollectedArgs) -> OutputArgs {
+    if let Some(output_args) = args.get::<OutputArgs>() {
+        return output_args.clone();
+    }
+    OutputArgs {
+        output: args.get::<OutputFormat>().copied().unwrap_or_default(),
+        ..Default::default()
+    }
+}
+
+pub(crate) fn render_with(value: &Value, args: &OutputArgs) -> String {
+    let projected;
+    let value = if args.fields.is_empty() {
+        value
+    } else {
+        projected = project(value, &args.fields);
+        &projected
+    };
+    match (&args.format, value) {
+        | (Some(template), Value::Array(items)) => {
+            items
+                .iter()
+                .map(|item| template.render(item))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        | (Some(template), value) => template.render(value),
+        | (None, value) => render_value(value, args.output),
+    }
+}
+
+/// Keeps only `fields` of objects, or of the objects in an array. Fields can
+/// be nested paths like `owner.id`.
+pub(crate) fn project(value: &Value, fields: &[String]) -> Value {
+    match value {
+        | Value::Array(items) => {
+            Value::Array(
+                items.iter().map(|item| project(item, fields)).collect(),
+            )
+        }
+        | Value::Object(_) => {
+            Value::Object(
+                fields
+                    .iter()
+                    .map(|field| {
+                        let path = parse_path(field);
+                        let value = lookup(value, &path)
+                            .cloned()
+                            .unwrap_or(Value::Null);
+                        (field.clone(), value)
+                    })
+                    .collect(),
+            )
+        }
+        | value => value.clone(),
+    }
+}
+
+pub(crate) fn to_value<T: Serialize>(value: &T) -> Result<Value, CliError> {
+    serde_json::to_value(value).map_err(|e| {
+        CliError::Other(
+            anyhow::Error::new(e).context("Failed to serialize output"),
+        )
+    })
+}
+
+fn render_value(value: &Value, format: OutputFormat) -> String {
+    match format {
+        | OutputFormat::Text => render_text(value),
+        | OutputFormat::Json => {
+            // Serializing a `Value` can't fail.
+            serde_json::to_string_pretty(value).unwrap_or_default()
+        }
+        | OutputFormat::Yaml => {
+            serde_norway::to_string(value).unwrap_or_default()
+        }
+        | OutputFormat::Table => render_table(value),
+        | OutputFormat::Csv => render_csv(value),
+    }
+}
+
+/// Scalars are printed as is, arrays one item per line, and objects one
+/// `key: value` pair per line.
+fn render_text(value: &Value) -> String {
+    match value {
+        | Value::Array(items) => {
+            items.iter().map(render_cell).collect::<Vec<_>>().join("\n")
+        }
+        | Value::Object(fields) => {
+            fields
+                .iter()
+                .map(|(key, value)| format!("{}: {}", key, render_cell(value)))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        | value => render_cell(value),
+    }
+}
+
+/// Arrays of objects get a column per key, objects get a row per key, and
+/// anything else is printed as text.
+fn render_table(value: &Value) -> String {
+    match value {
+        | Value::Array(items) if items.iter().any(|item| item.is_object()) => {
+            let columns = columns(items);
+            let header = columns.iter().map(|c| c.to_uppercase()).collect();
+            let rows = items.iter().map(|item| row(item, &columns)).collect();
+            format_table(Some(header), rows)
+        }
+        | Value::Object(fields) => {
+            let rows = fields
+                .iter()
+                .map(|(key, value)| {
+                    vec![key.to_uppercase(), render_cell(value)]
+                })
+                .collect();
+            format_table(None, rows)
+        }
+        | value => render_text(value),
+    }
+}
+
+/// Arrays of objects get a column per key, objects a single row, and
+/// anything else a single cell per line.
+fn render_csv(value: &Value) -> String {
+    let lines: Vec<String> = match value {
+        | Value::Array(items) if items.iter().any(|item| item.is_object()) => {
+            let columns = columns(items);
+            std::iter::once(csv_line(&columns))
+                .chain(items.iter().map(|item| csv_line(&row(item, &columns))))
+                .collect()
+        }
+        | Value::Array(items) => {
+            items
+                .iter()
+                .map(|item| csv_line(&[render_cell(item)]))
+                .collect()
+        }
+        | Value::Object(fields) => {
+            let columns: Vec<&String> = fields.keys().collect();
+            vec![csv_line(&columns), csv_line(&row(value, &columns))]
+        }
+        | value => vec![csv_line(&[render_cell(value)])],
+    };
+    lines.join("\n")
+}
+
+/// The keys of all objects in `items`, in the order they first appear.
+fn columns(items: &[Value]) -> Vec<String> {
+    let mut columns: Vec<String> = Vec::new();
+    for item in items {
+        if let Value::Object(fields) = item {
+            for key in fields.keys() {
+                if !columns.contains(key) {
+                    columns.push(key.clone());
+                }
+            }
+        }
+    }
+    columns
+}
+
+/// The cells of `item` for `columns`, empty if the item doesn't have them.
+pub(crate) fn row(item: &Value, columns: &[impl AsRef<str>]) -> Vec<String> {
+    columns
+        .iter()
+        .map(|column| {
+            item.get(column.as_ref())
+                .map(render_cell)
+                .unwrap_or_default()
+        })
+        .collect()
+}
+
+/// Joins cells with commas, quoting cells that contain commas, quotes, or
+/// line breaks.
+pub(crate) fn csv_line(cells: &[impl AsRef<str>]) -> String {
+    cells
+        .iter()
+        .map(|cell| {
+            let cell = cell.as_ref();
+            if cell.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell.to_owned()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+pub(crate) fn render_cell(value: &Value) -> String {
+    match value {
+        | Value::Null => String::new(),
+        | Value::String(s) => s.clone(),
+        | value => value.to_string(),
+    }
+}
+
+/// Aligns cells into columns separated by two spaces.
+pub(crate) fn format_table(
+    header: Option<Vec<String>>,
+    rows: Vec<Vec<String>>,
+) -> String {
+    let rows: Vec<Vec<String>> = header.into_iter().chain(rows).collect();
+    let widths = column_widths(&rows);
+    rows.iter()
+        .map(|row| format_row(row, &widths))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The width of the widest cell of each column.
+pub(crate) fn column_widths(rows: &[Vec<String>]) -> Vec<usize> {
+    let mut widths: Vec<usize> = Vec::new();
+    for row in rows {
+        for (i, cell) in row.iter().enumerate() {
+            let width = cell.chars().count();
+            match widths.get_mut(i) {
+                | Some(max) => *max = (*max).max(width),
+                | None => widths.push(width),
+            }
+        }
+    }
+    widths
+}
+
+/// Pads every cell but the last to the width of its column. Cells wider than
+/// their column are separated by two spaces as well.
+pub(crate) fn format_row(row: &[String], widths: &[usize]) -> String {
+    let mut line = String::new();
+    for (i, cell) in row.iter().enumerate() {
+        line.push_str(cell);
+        if i + 1 < row.len() {
+            let width = widths.get(i).copied().unwrap_or_default();
+            let padding = width.saturating_sub(cell.chars().count());
+            line.push_str(&" ".repeat(padding + 2));
+        }
+    }
+    line.trim_end().to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_render_table() {
+        let value = json!([
+            {"name": "cling", "stars": 100},
+            {"name": "clap", "stars": 15000, "archived": false},
+        ]);
+        assert_eq!(
+            "NAME   STARS  ARCHIVED\ncling  100\nclap   15000  false",
+            render_table(&value)
+        );
+        assert_eq!(
+            "NAME   cling\nSTARS  100",
+            render_table(&json!({"name": "cling", "stars": 100}))
+        );
+        assert_eq!("a\nb", render_table(&json!(["a", "b"])));
+    }
+
+    #[test]
+    fn test_render_with_fields_and_template() {
+        let value = json!([
+            {"name": "cling", "id": 1, "owner": {"id": 7}},
+            {"name": "clap", "id": 2},
+        ]);
+        let args = OutputArgs {
+            output: OutputFormat::Table,
+            fields: vec!["owner.id".to_owned(), "name".to_owned()],
+            ..Default::default()
+        };
+        assert_eq!(
+            "OWNER.ID  NAME\n7         cling\n          clap",
+            render_with(&value, &args)
+        );
+        let args = OutputArgs {
+            format: Some("{{.name}}\\t{{.id}}".parse().unwrap()),
+            ..Default::default()
+        };
+        assert_eq!("cling\t1\nclap\t2", render_with(&value, &args));
+        // Templates see the projected fields.
+        let args = OutputArgs {
+            format: Some("{{.name}}={{.id}}".parse().unwrap()),
+            fields: vec!["name".to_owned()],
+            ..Default::default()
+        };
+        assert_eq!("cling=\nclap=", render_with(&value, &args));
+    }
+
+    #[test]
+    fn test_render_csv() {
+        let value = json!([
+            {"name": "cling", "about": "Handlers, for clap"},
+            {"name": "say \"hi\"", "stars": 1},
+        ]);
+        assert_eq!(
+            "name,about,stars\ncling,\"Handlers, for clap\",\n\"say \
+             \"\"hi\"\"\",,1",
+            render_csv(&value)
+        );
+        assert_eq!("a\nb", render_csv(&json!(["a", "b"])));
+        assert_eq!("id,ok\n1,true", render_csv(&json!({"id": 1, "ok": true})));
+    }
+
+    #[test]
+    fn test_render_text() {
+        assert_eq!("hello", render_text(&json!("hello")));
+        assert_eq!("1\ntwo", render_text(&json!([1, "two"])));
+        assert_eq!(
+            "name: cling\ntags: [\"cli\"]",
+            render_text(&json!({"name": "cling", "tags": ["cli"]}))
+        );
+    }
+}
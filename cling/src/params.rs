@@ -1,4 +1,21 @@
-use crate::anymap::AnyMap;
+use std::any::TypeId;
+use std::collections::HashSet;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use indoc::formatdoc;
+
+use crate::anymap::{debug_fn, AnyMap, DebugFn};
+use crate::cancellation::Cancellation;
+use crate::dry_run::DryRun;
+use crate::effects::SetState;
+use crate::environment::Environment;
+use crate::error::CliError;
+use crate::extractors::State;
+use crate::layer::{Invocation, Layer};
+use crate::plan::{ExplainArgs, Plan, PlanStep};
+use crate::report::{ExecutionRecorder, ExecutionReport, TimingsArgs};
+use crate::timeout::Timer;
 
 // With the hope that one day marker traits attributes
 // [marker_trait_attr](https://github.com/rust-lang/rust/issues/29864) will be
@@ -16,6 +33,11 @@ impl<T> Collect for T where T: clap::Args {}
 
 impl<T> Collect for Option<T> where T: Collect {}
 impl<T> Collect for Vec<T> where T: Collect {}
+// Lets finally handlers extract the outcome of the command as
+// `&Result<(), CliError>` on nightly, where a dedicated extractor would
+// overlap with the impls above.
+#[cfg(unstable)]
+impl Collect for Result<(), CliError> {}
 
 // --
 // This is a clever trick inspired by "autref" specialization by
@@ -55,20 +77,197 @@ pub trait UnknownKind {
 // Note the type &T here, this means that it's lower priority.
 impl<T> UnknownKind for &T {}
 
-#[doc(hidden)]
-pub trait HandlerParam<'a>: Sized {
-    fn extract_param(args: &'a CollectedArgs) -> Option<Self>;
+// The same trick, for when only the type is known:
+// `(&CollectProbe::<T>::new()).is_collectable()`.
+pub struct CollectProbe<T>(PhantomData<T>);
+
+impl<T> CollectProbe<T> {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        CollectProbe(PhantomData)
+    }
+}
+
+pub trait CollectableType {
+    fn is_collectable(&self) -> bool {
+        true
+    }
+}
+
+impl<T> CollectableType for CollectProbe<T> where T: Collect {}
+
+pub trait UnknownType {
+    fn is_collectable(&self) -> bool {
+        false
+    }
+}
+
+impl<T> UnknownType for &CollectProbe<T> {}
+
+// And once more to find the `Debug` implementation of collected values, which
+// `CLING_DEBUG=collected` prints: `(&DebugProbe::<T>::new()).debug_fn()`.
+pub struct DebugProbe<T>(PhantomData<T>);
+
+impl<T> DebugProbe<T> {
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Self {
+        DebugProbe(PhantomData)
+    }
+
+    /// Probes the type of `value`, e.g. of an effect whose type is inferred.
+    pub fn of(_value: &T) -> Self {
+        DebugProbe(PhantomData)
+    }
+}
+
+pub trait DebuggableType {
+    fn debug_fn(&self) -> Option<DebugFn>;
+}
+
+impl<T> DebuggableType for DebugProbe<T>
+where
+    T: std::fmt::Debug + 'static,
+{
+    fn debug_fn(&self) -> Option<DebugFn> {
+        Some(debug_fn::<T>)
+    }
+}
+
+pub trait UnknownDebugType {
+    fn debug_fn(&self) -> Option<DebugFn> {
+        None
+    }
+}
+
+impl<T> UnknownDebugType for &DebugProbe<T> {}
+
+// The state of a `SetState` effect is stored as `State<S>`, whose formatter
+// is found with `(&DebugProbe::of(&effect)).state_debug_fn()`.
+pub trait DebuggableState {
+    fn state_debug_fn(&self) -> Option<DebugFn>;
+}
+
+impl<S> DebuggableState for DebugProbe<SetState<S>>
+where
+    S: Clone + Send + Sync + std::fmt::Debug + 'static,
+{
+    fn state_debug_fn(&self) -> Option<DebugFn> {
+        Some(debug_fn::<State<S>>)
+    }
+}
+
+pub trait UnknownDebugState {
+    fn state_debug_fn(&self) -> Option<DebugFn> {
+        None
+    }
+}
+
+impl<T> UnknownDebugState for &DebugProbe<T> {}
+
+/// Extracts a handler parameter from the [CollectedArgs] of the command.
+///
+/// This is how [State], [Collected](crate::Collected), references to
+/// collected types and the other built-in extractors are implemented. Handlers
+/// can accept any type that implements it. When the extraction fails, the
+/// [Rejection](FromCollected::Rejection) is converted into the [CliError] of
+/// the handler, with its message and exit code.
+///
+/// Example:
+/// ```rust
+/// use cling::prelude::*;
+/// use cling::{CollectedArgs, FromCollected};
+///
+/// #[derive(Args, Collect, Debug, Clone)]
+/// pub struct AuthArgs {
+///     #[arg(long)]
+///     token: Option<String>,
+/// }
+///
+/// pub struct AuthenticatedUser(String);
+///
+/// impl<'a> FromCollected<'a> for AuthenticatedUser {
+///     type Rejection = CliError;
+///
+///     fn from_collected(args: &'a CollectedArgs) -> Result<Self, CliError> {
+///         match args.get::<AuthArgs>().and_then(|auth| auth.token.as_ref()) {
+///             | Some(token) => Ok(AuthenticatedUser(format!("user-{}", token))),
+///             | None => {
+///                 Err(CliError::FailedWithMessageAndCode(
+///                     "Please log in with --token".to_owned(),
+///                     77,
+///                 ))
+///             }
+///         }
+///     }
+/// }
+///
+/// fn whoami(AuthenticatedUser(user): AuthenticatedUser) {
+///     println!("{}", user);
+/// }
+/// ```
+pub trait FromCollected<'a>: Sized {
+    /// The error of the handler when the extraction fails.
+    type Rejection: Into<CliError>;
+
+    fn from_collected(args: &'a CollectedArgs)
+        -> Result<Self, Self::Rejection>;
 }
 
 /// Blanked implementation that allows handlers to accept shared references to
 /// any collectable type.
-impl<'a, T> HandlerParam<'a> for &'a T
+impl<'a, T> FromCollected<'a> for &'a T
 where
     T: Sync + Send + 'static,
     T: Collect,
 {
-    fn extract_param(args: &'a CollectedArgs) -> Option<Self> {
-        args.get::<T>()
+    type Rejection = NotCollected;
+
+    fn from_collected(args: &'a CollectedArgs) -> Result<Self, NotCollected> {
+        let value = args.get::<T>();
+        #[cfg(unstable)]
+        let value = value.or_else(|| crate::extractors::Outcome::get_as(args));
+        value.ok_or_else(|| NotCollected::new::<Self>(args))
+    }
+}
+
+/// Rejection of the built-in extractors when the value they extract was not
+/// collected. Converts into [CliError::InvalidHandler].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct NotCollected {
+    /// Name of the type that couldn't be extracted.
+    pub type_name: &'static str,
+    /// Names of the types that were collected, sorted.
+    pub collected: Vec<String>,
+}
+
+impl NotCollected {
+    /// `T` was not found in `args`.
+    pub fn new<T>(args: &CollectedArgs) -> Self {
+        let mut collected = args.collected_types();
+        collected.sort();
+        Self {
+            type_name: std::any::type_name::<T>(),
+            collected,
+        }
+    }
+}
+
+impl From<NotCollected> for CliError {
+    fn from(value: NotCollected) -> Self {
+        CliError::InvalidHandler(formatdoc! {"
+            Type `{}` was not collected from input arguments. Possible reasons:
+               - The type doesn't implement `Collect` (add #[derive(Collect)])
+               - The struct field wasn't marked with `#[cling(collect)]`
+               - The type is not present in any fields, enums, or structs leading to this command in the command hierarchy.
+               - The type is defined with Option<T> or Vec<T> and you used T, or vice versa
+
+               Those are the types that have been collected: {:#?}
+               "
+            ,
+            value.type_name,
+            value.collected,
+        })
     }
 }
 
@@ -80,12 +279,129 @@ where
 #[derive(Default)]
 pub struct CollectedArgs {
     map: Option<AnyMap>,
+    // Types of the values that were inserted as `State<S>`.
+    state_types: HashSet<TypeId>,
+    context: RunContext,
+}
+
+/// How the command runs, kept apart from the values it collected.
+#[derive(Default)]
+struct RunContext {
+    layers: Option<Arc<[Arc<dyn Layer>]>>,
+    // Depth of the command that is currently running.
+    level: usize,
+    // Names of the commands leading to the one that is currently running.
+    command_path: Vec<String>,
+    // Commands at this level or above ran before in the session, their
+    // `#[cling(run = ...)]` handlers are not executed and their arguments are
+    // not collected again.
+    resumed_through: Option<usize>,
+    // Set when running to compute completion candidates instead of handlers.
+    completion: Option<CompletionRequest>,
+    cancellation: Option<Cancellation>,
+    timer: Option<Arc<dyn Timer>>,
+    dry_run: Option<DryRun>,
+    environment: Option<Environment>,
+    // Set while planning, handlers are recorded here instead of running.
+    plan: Option<Vec<PlanStep>>,
+    // Set when the handlers that run are recorded for an execution report.
+    recorder: Option<Arc<ExecutionRecorder>>,
+}
+
+/// The argument whose value is being completed.
+#[cfg_attr(not(feature = "completions"), allow(dead_code))]
+pub(crate) struct CompletionRequest {
+    // Level of the command that defines the argument, or `None` for global
+    // arguments.
+    level: Option<usize>,
+    pub(crate) arg_id: String,
+    candidates: Option<Vec<String>>,
+}
+
+impl CompletionRequest {
+    #[cfg(feature = "completions")]
+    pub(crate) fn new(level: Option<usize>, arg_id: String) -> Self {
+        Self {
+            level,
+            arg_id,
+            candidates: None,
+        }
+    }
+
+    fn targets(&self, level: usize, arg_id: &str) -> bool {
+        self.level.map_or(true, |l| l == level) && self.arg_id == arg_id
+    }
+}
+
+/// Inserts a clone of a state, for runs that each start from the same states.
+pub(crate) type InsertState = Arc<dyn Fn(&mut CollectedArgs) + Send + Sync>;
+
+/// Builder of [CollectedArgs], created with [CollectedArgs::builder].
+#[derive(Default)]
+pub struct CollectedArgsBuilder {
+    args: CollectedArgs,
+}
+
+impl CollectedArgsBuilder {
+    /// Adds a clone of `value` as if it was collected from the command line.
+    pub fn with<T>(mut self, value: &T) -> Self
+    where
+        T: Collect + Clone + Send + Sync + 'static,
+    {
+        self.args
+            .insert(value.clone(), /* override_is_expected = */ true);
+        self
+    }
+
+    /// Adds a state that handlers can extract with [State].
+    pub fn with_state<S>(mut self, state: S) -> Self
+    where
+        S: Clone + Send + Sync + std::fmt::Debug + 'static,
+    {
+        self.args.insert_debug_state(state);
+        self
+    }
+
+    pub fn build(self) -> CollectedArgs {
+        self.args
+    }
 }
 
 impl CollectedArgs {
     #[inline]
     pub fn new() -> Self {
-        CollectedArgs { map: None }
+        CollectedArgs {
+            map: None,
+            state_types: HashSet::new(),
+            context: RunContext::default(),
+        }
+    }
+
+    /// Builds collected arguments by hand, e.g. to call a handler in a unit
+    /// test without parsing a command line.
+    ///
+    /// Example:
+    /// ```rust
+    /// use cling::prelude::*;
+    /// use cling::CollectedArgs;
+    ///
+    /// #[derive(Collect, Args, Debug, Clone)]
+    /// pub struct Options {
+    ///     #[arg(long)]
+    ///     pub verbose: bool,
+    /// }
+    ///
+    /// let args = CollectedArgs::builder()
+    ///     .with(&Options { verbose: true })
+    ///     .with_state(42_u32)
+    ///     .build();
+    /// assert!(args.get::<Options>().unwrap().verbose);
+    /// assert_eq!(Some(&State(42_u32)), args.get::<State<u32>>());
+    /// ```
+    pub fn builder() -> CollectedArgsBuilder {
+        CollectedArgsBuilder {
+            args: CollectedArgs::new(),
+        }
     }
 
     pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
@@ -103,7 +419,22 @@ impl CollectedArgs {
         val: T,
         override_is_expected: bool,
     ) {
-        let res = self.map.get_or_insert_with(Default::default).insert(val);
+        self.insert_with_debug(val, override_is_expected, None);
+    }
+
+    /// Like [CollectedArgs::insert], with the `Debug` formatter of the value
+    /// found by [DebugProbe].
+    #[doc(hidden)]
+    pub fn insert_with_debug<T: Send + Sync + 'static>(
+        &mut self,
+        val: T,
+        override_is_expected: bool,
+        debug: Option<DebugFn>,
+    ) {
+        let res = self
+            .map
+            .get_or_insert_with(Default::default)
+            .insert_with_debug(val, debug);
         if res.is_some() && !override_is_expected {
             // We have collected the same type twice, we overwrite with the
             // newest value but we must inform the user/dev about it
@@ -119,11 +450,92 @@ impl CollectedArgs {
         }
     }
 
+    /// Inserts a state that can be extracted with [State] in handlers. If a
+    /// state of the same type exists, it will be replaced.
+    pub fn insert_state<S: Clone + Send + Sync + 'static>(&mut self, state: S) {
+        self.insert_state_with_debug(state, None);
+    }
+
+    /// Like [CollectedArgs::insert_state], with the `Debug` formatter of
+    /// `State<S>`.
+    #[doc(hidden)]
+    pub fn insert_state_with_debug<S: Clone + Send + Sync + 'static>(
+        &mut self,
+        state: S,
+        debug: Option<DebugFn>,
+    ) {
+        self.state_types.insert(TypeId::of::<State<S>>());
+        self.insert_with_debug(
+            State(state),
+            /* override_is_expected = */ true,
+            debug,
+        );
+    }
+
+    /// Inserts a state whose type is known to implement `Debug`.
+    pub(crate) fn insert_debug_state<S>(&mut self, state: S)
+    where
+        S: Clone + Send + Sync + std::fmt::Debug + 'static,
+    {
+        self.insert_state_with_debug(state, Some(debug_fn::<State<S>>));
+    }
+
+    /// Moves all states out of this [CollectedArgs], leaving the collected
+    /// arguments in place.
+    pub fn take_states(&mut self) -> CollectedArgs {
+        let state_types = std::mem::take(&mut self.state_types);
+        let map = self
+            .map
+            .as_mut()
+            .map(|map| map.split_off(|type_id| state_types.contains(type_id)));
+        CollectedArgs {
+            map,
+            state_types,
+            ..CollectedArgs::new()
+        }
+    }
+
+    /// Removes the value of type T if exists and returns it.
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.map.as_mut().and_then(|map| map.remove())
+    }
+
     #[inline]
     pub fn clear(&mut self) {
         if let Some(ref mut map) = self.map {
             map.clear();
         }
+        self.state_types.clear();
+    }
+
+    /// Type ids of the values, states included.
+    #[cfg(feature = "repl")]
+    pub(crate) fn type_ids(&self) -> HashSet<TypeId> {
+        self.map
+            .as_ref()
+            .map_or(HashSet::new(), |map| map.type_ids())
+    }
+
+    /// Moves the values whose type is in `type_ids` into a new
+    /// [CollectedArgs].
+    #[cfg(feature = "repl")]
+    pub(crate) fn split_off(&mut self, type_ids: &HashSet<TypeId>) -> Self {
+        CollectedArgs {
+            map: self
+                .map
+                .as_mut()
+                .map(|map| map.split_off(|type_id| type_ids.contains(type_id))),
+            ..CollectedArgs::new()
+        }
+    }
+
+    /// Moves the values and states of `other` into this [CollectedArgs].
+    #[cfg(feature = "repl")]
+    pub(crate) fn absorb(&mut self, other: CollectedArgs) {
+        if let Some(map) = other.map {
+            self.map.get_or_insert_with(Default::default).absorb(map);
+        }
+        self.state_types.extend(other.state_types);
     }
 
     pub fn collected_types(&self) -> Vec<String> {
@@ -136,4 +548,198 @@ impl CollectedArgs {
     pub fn is_empty(&self) -> bool {
         self.map.as_ref().map_or(true, |map| map.is_empty())
     }
+
+    /// The type name and `Debug` representation of every collected value.
+    pub(crate) fn debug_values(&self) -> Vec<(&'static str, String)> {
+        self.map
+            .as_ref()
+            .map_or(Vec::new(), |map| map.debug_values())
+    }
+
+    pub(crate) fn set_layers(&mut self, layers: Vec<Arc<dyn Layer>>) {
+        self.context.layers = if layers.is_empty() {
+            None
+        } else {
+            Some(layers.into())
+        };
+    }
+
+    pub(crate) fn layers(&self) -> Option<Arc<[Arc<dyn Layer>]>> {
+        self.context.layers.clone()
+    }
+
+    /// Records the handlers that run from now on. The recorder is the
+    /// innermost layer, so handlers are timed without the layers that wrap
+    /// them.
+    pub(crate) fn record_execution(&mut self) {
+        if self.context.recorder.is_some() {
+            return;
+        }
+        let recorder = Arc::new(ExecutionRecorder::default());
+        let mut layers = self
+            .context
+            .layers
+            .as_deref()
+            .map_or(Vec::new(), <[_]>::to_vec);
+        layers.push(recorder.clone());
+        self.context.layers = Some(layers.into());
+        self.context.recorder = Some(recorder);
+    }
+
+    /// Whether `--timings` asked for an execution report.
+    pub(crate) fn is_timed(&self) -> bool {
+        self.get::<TimingsArgs>().is_some_and(|args| args.timings)
+    }
+
+    pub(crate) fn execution_report(&self) -> ExecutionReport {
+        self.context
+            .recorder
+            .as_ref()
+            .map(|recorder| recorder.report())
+            .unwrap_or_default()
+    }
+
+    #[doc(hidden)]
+    pub fn level(&self) -> usize {
+        self.context.level
+    }
+
+    #[doc(hidden)]
+    pub fn set_level(&mut self, level: usize) {
+        self.context.level = level;
+    }
+
+    #[doc(hidden)]
+    pub fn push_command(&mut self, name: &str) {
+        self.context.command_path.push(name.to_owned());
+    }
+
+    #[doc(hidden)]
+    pub fn pop_command(&mut self) {
+        self.context.command_path.pop();
+    }
+
+    /// Names of the commands leading to the running one, e.g. `app list`.
+    pub(crate) fn command_path(&self) -> String {
+        self.context.command_path.join(" ")
+    }
+
+    #[cfg(feature = "repl")]
+    pub(crate) fn resume_through(&mut self, level: Option<usize>) {
+        self.context.resumed_through = level;
+    }
+
+    /// Whether the command at `level` ran before in the session, see
+    /// `Repl`.
+    #[doc(hidden)]
+    pub fn is_resumed_at(&self, level: usize) -> bool {
+        self.context
+            .resumed_through
+            .is_some_and(|through| level <= through)
+    }
+
+    pub(crate) fn set_timer(&mut self, timer: Option<Arc<dyn Timer>>) {
+        self.context.timer = timer;
+    }
+
+    /// The timer that enforces handler timeouts, if one was configured.
+    #[doc(hidden)]
+    pub fn timer(&self) -> Option<Arc<dyn Timer>> {
+        self.context.timer.clone()
+    }
+
+    pub(crate) fn set_cancellation(&mut self, cancellation: Cancellation) {
+        self.context.cancellation = Some(cancellation);
+    }
+
+    pub(crate) fn cancellation(&self) -> Option<&Cancellation> {
+        self.context.cancellation.as_ref()
+    }
+
+    pub(crate) fn set_environment(&mut self, environment: Environment) {
+        self.context.environment = Some(environment);
+    }
+
+    /// The environment of the command, the process' unless a test replaced
+    /// it.
+    pub(crate) fn environment(&self) -> Environment {
+        self.context.environment.clone().unwrap_or_default()
+    }
+
+    pub(crate) fn set_dry_run(&mut self, dry_run: DryRun) {
+        self.context.dry_run = Some(dry_run);
+    }
+
+    /// The dry-run mode of the command, enabled if `--dry-run` was collected.
+    pub(crate) fn dry_run(&self) -> DryRun {
+        self.context
+            .dry_run
+            .clone()
+            .unwrap_or_default()
+            .resolve(self)
+    }
+
+    pub(crate) fn start_planning(&mut self) {
+        self.context.plan = Some(Vec::new());
+    }
+
+    /// Whether handlers are planned instead of run. Planning also starts when
+    /// `--explain` is collected.
+    pub(crate) fn is_planning(&self) -> bool {
+        self.context.plan.is_some()
+            || self.get::<ExplainArgs>().is_some_and(|args| args.explain)
+    }
+
+    pub(crate) fn plan_step(&mut self, invocation: &Invocation) {
+        if self.is_planning() {
+            let step = PlanStep::new(invocation);
+            self.context.plan.get_or_insert_with(Vec::new).push(step);
+        }
+    }
+
+    pub(crate) fn take_plan(&mut self) -> Option<Plan> {
+        let planning = self.is_planning();
+        let steps = self.context.plan.take();
+        planning.then(|| {
+            Plan {
+                steps: steps.unwrap_or_default(),
+            }
+        })
+    }
+
+    #[cfg(feature = "completions")]
+    pub(crate) fn start_completion(&mut self, request: CompletionRequest) {
+        self.context.completion = Some(request);
+    }
+
+    pub(crate) fn is_completing(&self) -> bool {
+        self.context.completion.is_some()
+    }
+
+    /// Whether the value of argument `arg_id` of the command that is currently
+    /// running is being completed.
+    #[doc(hidden)]
+    pub fn completes_arg(&self, arg_id: &str) -> bool {
+        self.context
+            .completion
+            .as_ref()
+            .is_some_and(|request| request.targets(self.context.level, arg_id))
+    }
+
+    pub(crate) fn add_candidates(&mut self, candidates: Vec<String>) {
+        if let Some(request) = self.context.completion.as_mut() {
+            request
+                .candidates
+                .get_or_insert_with(Vec::new)
+                .extend(candidates);
+        }
+    }
+
+    #[cfg(feature = "completions")]
+    pub(crate) fn take_candidates(&mut self) -> Option<Vec<String>> {
+        self.context
+            .completion
+            .take()
+            .and_then(|request| request.candidates)
+    }
 }
@@ -0,0 +1,118 @@
+//! Description of the handlers a command line would run, without running
+//! them.
+use std::fmt::{self, Display, Formatter};
+
+use clap::Args;
+
+use crate::layer::{Invocation, InvocationKind};
+use crate::metadata::short_type_name;
+use crate::params::Collect;
+
+/// A global `--explain` flag that prints the [Plan] of the command instead of
+/// running its handlers.
+///
+/// Flatten it into the top-level command. The flag takes effect once it's
+/// collected, so layers still wrap the dispatch of the top-level command.
+/// Use `Cling::plan()` to bypass all layers.
+#[derive(Args, Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExplainArgs {
+    /// Print the handlers the command would run instead of running them
+    #[arg(long, global = true)]
+    pub explain: bool,
+}
+
+impl Collect for ExplainArgs {}
+
+/// The handlers a command line would run, in order. Returned by
+/// `Cling::plan()`.
+///
+/// Example:
+/// ```rust
+/// use cling::prelude::*;
+///
+/// #[derive(Run, Collect, Parser, Debug, Clone)]
+/// #[cling(run = "init")]
+/// pub struct App {
+///     #[command(subcommand)]
+///     pub cmd: Commands,
+/// }
+///
+/// #[derive(Run, Subcommand, Debug, Clone)]
+/// pub enum Commands {
+///     #[cling(run = "build")]
+///     Build,
+/// }
+///
+/// fn init(_app: &App) {}
+/// fn build() {
+///     unreachable!("handlers don't run while planning");
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let plan = Cling::<App>::try_parse_from(["app", "build"])
+///     .unwrap()
+///     .plan()
+///     .await
+///     .unwrap();
+/// let handlers: Vec<_> = plan.steps.iter().map(|step| step.handler).collect();
+/// assert_eq!(vec!["init", "build"], handlers);
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Plan {
+    pub steps: Vec<PlanStep>,
+}
+
+/// A handler in a [Plan].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct PlanStep {
+    pub kind: InvocationKind,
+    /// Path of the handler function as written in the attribute.
+    pub handler: &'static str,
+    /// Name of the [Run](crate::Run) type the handler is attached to.
+    pub type_name: &'static str,
+    /// Depth of the command in the command tree.
+    pub level: usize,
+    /// Types of the handler's parameters, in order.
+    pub params: Vec<String>,
+}
+
+impl PlanStep {
+    pub(crate) fn new(invocation: &Invocation) -> Self {
+        Self {
+            kind: invocation.kind(),
+            handler: invocation.handler_name().unwrap_or_default(),
+            type_name: invocation.type_name(),
+            level: invocation.level(),
+            params: invocation
+                .param_types()
+                .iter()
+                .map(|param| short_type_name(param))
+                .collect(),
+        }
+    }
+}
+
+impl Display for Plan {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.steps.is_empty() {
+            return writeln!(f, "No handlers would run.");
+        }
+        for (i, step) in self.steps.iter().enumerate() {
+            writeln!(
+                f,
+                "{}. {} `{}({})` of `{}` (level {})",
+                i + 1,
+                step.kind,
+                step.handler,
+                step.params.join(", "),
+                step.type_name,
+                step.level
+            )?;
+        }
+        Ok(())
+    }
+}
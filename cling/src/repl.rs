@@ -0,0 +1,658 @@
+//! A built-in REPL for running cling applications interactively.
+use std::any::TypeId;
+use std::collections::HashSet;
+use std::future::Future;
+use std::io::Write;
+use std::marker::PhantomData;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use clap::{Command, Parser};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+use crate::app::{Cling, ClingFinished, Run, Settings};
+use crate::complete::Completion;
+use crate::error::{format_clap_error, CliError};
+use crate::executor::block_on;
+use crate::layer::{BoxFuture, Layer, Next};
+use crate::params::{CollectedArgs, InsertState};
+
+/// Commands that are handled by the REPL itself.
+const BUILTINS: &[&str] = &["exit", "help"];
+/// Leaves the current scope.
+const PARENT_SCOPE: &str = "..";
+
+/// An interactive shell that runs commands of `T`.
+///
+/// Each line is parsed as a command of `T` (without the binary name) and
+/// executed with the same handlers as the command-line program. State set by
+/// handlers via [SetState](crate::SetState) or by returning
+/// [State](crate::State) is kept across lines. Subcommands and flags are
+/// completed with the TAB key.
+///
+/// The REPL understands two built-in commands: `exit` to leave the REPL and
+/// `help` to print the help of `T`.
+///
+/// ## Scopes
+/// Entering a command that has subcommands without selecting one of them
+/// (e.g. `calculator --color` if `calculator` has `add` and `subtract`
+/// subcommands) enters the scope of that command. Subsequent lines are parsed
+/// relative to it (e.g. `add 1 2`), and the prompt shows the current path.
+/// `..` leaves the scope.
+///
+/// The scope and its parents are run once, by the first command of the scope
+/// that gets past their `#[cling(run = ...)]` handlers. The arguments they
+/// collected are reused for the following commands of the scope, which skip
+/// their `run` handlers. `after` and `finally` handlers run for every
+/// command.
+///
+/// Example:
+/// ```rust, no_run
+/// use cling::prelude::*;
+///
+/// #[derive(Run, Parser, Debug, Clone)]
+/// pub struct App {
+///     #[command(subcommand)]
+///     pub cmd: Commands,
+/// }
+///
+/// #[derive(Run, Subcommand, Debug, Clone)]
+/// pub enum Commands {
+///     #[cling(run = "beep")]
+///     Beep,
+/// }
+///
+/// fn beep() {
+///     println!("Beep beep!");
+/// }
+///
+/// #[tokio::main]
+/// async fn main() -> ClingFinished<App> {
+///     Cling::<App>::repl().prompt("app> ").run().await
+/// }
+/// ```
+pub struct Repl<T> {
+    prompt: String,
+    history_file: Option<PathBuf>,
+    settings: Settings,
+    states: CollectedArgs,
+    // The initial states, for completion functions.
+    initial_states: Vec<InsertState>,
+    scopes: Vec<Scope>,
+    _parsed_type: PhantomData<T>,
+}
+
+/// A subcommand group entered in the REPL.
+struct Scope {
+    /// The arguments that entered this scope.
+    args: Vec<String>,
+    /// Names of the subcommands in this scope, relative to the parent scope.
+    path: Vec<String>,
+    /// The values collected by the commands of this scope, below the parent
+    /// scope, once they ran.
+    collected: Option<CollectedArgs>,
+}
+
+impl<T: Run + Parser> Cling<T> {
+    /// Creates a [Repl] that runs commands of `T` interactively.
+    pub fn repl() -> Repl<T> {
+        Repl {
+            prompt: ">> ".to_owned(),
+            history_file: None,
+            settings: Settings::default(),
+            states: CollectedArgs::new(),
+            initial_states: Vec::new(),
+            scopes: Vec::new(),
+            _parsed_type: PhantomData,
+        }
+    }
+}
+
+impl<T: Run + Parser> Repl<T> {
+    /// Sets the prompt printed before every line. Defaults to `>> `.
+    pub fn prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
+
+    /// Loads the line history from `path` when the REPL starts and saves it
+    /// back when the REPL exits.
+    pub fn history_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.history_file = Some(path.into());
+        self
+    }
+
+    /// Sets the initial state, it can be extracted with
+    /// [State](crate::State) in handlers and completion functions.
+    ///
+    /// Completion functions only see the initial states, not the states set
+    /// by the commands that ran.
+    pub fn with_state<S>(mut self, state: S) -> Self
+    where
+        S: Clone + Send + Sync + std::fmt::Debug + 'static,
+    {
+        self.states.insert_debug_state(state.clone());
+        self.initial_states
+            .push(Arc::new(move |args: &mut CollectedArgs| {
+                args.insert_debug_state(state.clone())
+            }));
+        self
+    }
+
+    /// Wraps every command dispatch and handler call in `layer`. See
+    /// [Cling::layer].
+    pub fn layer(mut self, layer: impl Layer) -> Self {
+        self.settings.layers.push(Arc::new(layer));
+        self
+    }
+
+    /// Runs the REPL until `exit` is entered or the input is closed
+    /// (CTRL-D). Errors of individual commands are printed and do not stop
+    /// the REPL.
+    ///
+    /// The returned [ClingFinished] holds the states that were set when the
+    /// REPL exited.
+    ///
+    /// With the `tokio` feature, when the REPL runs on a tokio runtime, lines
+    /// are read on a blocking thread of that runtime and completion functions
+    /// run on it, so they can use its IO and timers. Otherwise, completion
+    /// functions run on a minimal built-in executor and must not depend on a
+    /// runtime.
+    pub async fn run(mut self) -> ClingFinished<T>
+    where
+        T: 'static,
+    {
+        let mut editor = match ReplEditor::<T>::new() {
+            | Ok(editor) => editor,
+            | Err(e) => return Cling::failed(CliError::Other(e.into())),
+        };
+        editor.set_helper(Some(ReplHelper {
+            completion: Completion::new(
+                self.settings.clone(),
+                self.initial_states.clone(),
+            ),
+            scope: Vec::new(),
+            #[cfg(feature = "tokio")]
+            runtime: tokio::runtime::Handle::try_current().ok(),
+        }));
+        if let Some(path) = &self.history_file {
+            // The history file doesn't exist on first run.
+            let _ = editor.load_history(path);
+        }
+
+        let result = loop {
+            let prompt = self.scoped_prompt();
+            let line;
+            (editor, line) = read_line(editor, prompt).await;
+            let line = match line {
+                | Ok(line) => line,
+                // CTRL-C discards the current line.
+                | Err(ReadlineError::Interrupted) => continue,
+                | Err(ReadlineError::Eof) => break Ok(()),
+                | Err(e) => break Err(CliError::Other(e.into())),
+            };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let _ = editor.add_history_entry(line);
+            match line {
+                | "exit" => break Ok(()),
+                | "help" => {
+                    print_help(T::command(), &self.scope_path());
+                    continue;
+                }
+                | PARENT_SCOPE => {
+                    self.scopes.pop();
+                }
+                | _ => {
+                    if let Err(e) = self.run_line(line).await {
+                        let _ = e.print();
+                    }
+                }
+            }
+            if let Some(helper) = editor.helper_mut() {
+                helper.scope = self.scope_path();
+            }
+        };
+
+        if let Some(path) = &self.history_file {
+            let _ = editor.save_history(path);
+        }
+        Cling::with_result(self.settings, result, self.states)
+    }
+
+    /// Parses and runs a single line, keeping the states it sets. If the line
+    /// selects a command that has subcommands, its scope is entered instead.
+    async fn run_line(&mut self, line: &str) -> Result<(), CliError> {
+        let line_args = shlex::split(line).ok_or(CliError::InputString)?;
+        let command = T::command();
+        let bin_name = command.get_bin_name().unwrap_or(command.get_name());
+        let args: Vec<String> = std::iter::once(bin_name.to_owned())
+            .chain(self.scopes.iter().flat_map(|scope| scope.args.clone()))
+            .chain(line_args.clone())
+            .collect();
+
+        let depth = self.scope_depth();
+        if let Some(path) = group_path(command, &args) {
+            if path.len() > depth {
+                self.scopes.push(Scope {
+                    args: line_args,
+                    path: path[depth..].to_vec(),
+                    collected: None,
+                });
+                return Ok(());
+            }
+        }
+
+        let parsed = T::try_parse_from(args).map_err(format_clap_error::<T>)?;
+        let depths = self.scope_depths();
+        // The scopes that ran before are resumed with the values they
+        // collected.
+        let resumed = self
+            .scopes
+            .iter()
+            .take_while(|scope| scope.collected.is_some())
+            .count();
+        let mut args = std::mem::take(&mut self.states);
+        let mut resumed_type_ids = Vec::new();
+        for scope in &mut self.scopes[..resumed] {
+            let collected = scope.collected.take().unwrap_or_default();
+            resumed_type_ids.push(collected.type_ids());
+            args.absorb(collected);
+        }
+        if resumed > 0 {
+            args.resume_through(Some(depths[resumed - 1]));
+        }
+        let mut settings = self.settings.clone();
+        let recorder = Arc::new(ScopeRecorder::new(depths[resumed..].to_vec()));
+        settings.layers.push(recorder.clone());
+
+        let finished = Cling::with_settings(parsed, settings, args).run().await;
+        let (_, mut args) = finished.print_report();
+        self.states = args.take_states();
+        for (scope, type_ids) in self.scopes.iter_mut().zip(resumed_type_ids) {
+            scope.collected = Some(args.split_off(&type_ids));
+        }
+        // Outer scopes are split off first, so each scope keeps the values
+        // collected below its parent.
+        for (scope, type_ids) in
+            self.scopes[resumed..].iter_mut().zip(recorder.type_ids())
+        {
+            scope.collected = Some(args.split_off(&type_ids));
+        }
+        Ok(())
+    }
+
+    /// Names of the subcommands leading to the current scope.
+    fn scope_path(&self) -> Vec<String> {
+        self.scopes
+            .iter()
+            .flat_map(|scope| scope.path.iter().cloned())
+            .collect()
+    }
+
+    /// Level of the command of the current scope.
+    fn scope_depth(&self) -> usize {
+        self.scopes.iter().map(|scope| scope.path.len()).sum()
+    }
+
+    /// Level of the command of every scope.
+    fn scope_depths(&self) -> Vec<usize> {
+        self.scopes
+            .iter()
+            .scan(0, |depth, scope| {
+                *depth += scope.path.len();
+                Some(*depth)
+            })
+            .collect()
+    }
+
+    fn scoped_prompt(&self) -> String {
+        if self.scopes.is_empty() {
+            self.prompt.clone()
+        } else {
+            format!("{} {}", self.scope_path().join(" "), self.prompt)
+        }
+    }
+}
+
+/// Records the types that were collected when dispatch first goes below the
+/// command at each of the `depths`, i.e. once the command and its parents
+/// ran.
+struct ScopeRecorder {
+    depths: Vec<usize>,
+    type_ids: Mutex<Vec<HashSet<TypeId>>>,
+}
+
+impl ScopeRecorder {
+    fn new(depths: Vec<usize>) -> Self {
+        Self {
+            depths,
+            type_ids: Mutex::default(),
+        }
+    }
+
+    /// The recorded types, for the outermost depths that dispatch got below.
+    fn type_ids(&self) -> Vec<HashSet<TypeId>> {
+        std::mem::take(&mut *self.type_ids.lock().unwrap())
+    }
+}
+
+impl Layer for ScopeRecorder {
+    fn call<'a>(
+        self: Arc<Self>,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<(), CliError>> {
+        {
+            let mut type_ids = self.type_ids.lock().unwrap();
+            let level = next.invocation().level();
+            while type_ids.len() < self.depths.len()
+                && level > self.depths[type_ids.len()]
+            {
+                type_ids.push(next.args().type_ids());
+            }
+        }
+        next.run()
+    }
+}
+
+/// If `args` select a command that has subcommands without selecting one of
+/// them, returns the names of the subcommands leading to it.
+fn group_path(command: Command, args: &[String]) -> Option<Vec<String>> {
+    let mut command = without_required_subcommands(command);
+    let matches = command.try_get_matches_from_mut(args).ok()?;
+
+    let mut path = Vec::new();
+    let mut current: &Command = &command;
+    let mut matches = &matches;
+    while let Some((name, sub_matches)) = matches.subcommand() {
+        current = current.find_subcommand(name)?;
+        path.push(name.to_owned());
+        matches = sub_matches;
+    }
+    current.has_subcommands().then_some(path)
+}
+
+fn without_required_subcommands(mut command: Command) -> Command {
+    let names: Vec<String> = command
+        .get_subcommands()
+        .map(|subcommand| subcommand.get_name().to_owned())
+        .collect();
+    command = command
+        .subcommand_required(false)
+        .arg_required_else_help(false);
+    for name in names {
+        command = command.mut_subcommand(name, without_required_subcommands);
+    }
+    command
+}
+
+fn print_help(mut command: Command, scope: &[String]) {
+    command.build();
+    let command = scope.iter().try_fold(&mut command, |command, name| {
+        command.find_subcommand_mut(name)
+    });
+    let mut help = String::new();
+    if let Some(command) = command {
+        help.push_str(&format!("{}\n", command.render_help()));
+    }
+    help.push_str("REPL commands:\n");
+    help.push_str("  exit  Leave the REPL\n");
+    help.push_str("  help  Print this message\n");
+    if !scope.is_empty() {
+        help.push_str("  ..    Leave the current scope\n");
+    }
+    let _ = crate::console::stdout().write_all(help.as_bytes());
+}
+
+type ReplEditor<T> = Editor<ReplHelper<T>, DefaultHistory>;
+
+/// Reads a line with `editor`. On a tokio runtime, the line is read on a
+/// blocking thread, so the runtime keeps driving the IO and timers that
+/// completion functions may wait on.
+async fn read_line<T: Run + Parser + 'static>(
+    mut editor: ReplEditor<T>,
+    prompt: String,
+) -> (ReplEditor<T>, rustyline::Result<String>) {
+    #[cfg(feature = "tokio")]
+    if let Some(runtime) = editor.helper().and_then(|h| h.runtime.clone()) {
+        let read = runtime.spawn_blocking(move || {
+            let line = editor.readline(&prompt);
+            (editor, line)
+        });
+        return match read.await {
+            | Ok(read) => read,
+            | Err(e) => std::panic::resume_unwind(e.into_panic()),
+        };
+    }
+    let line = editor.readline(&prompt);
+    (editor, line)
+}
+
+/// Completes command lines like the completion scripts, including the values
+/// computed by `#[cling(complete = ...)]` functions.
+struct ReplHelper<T> {
+    completion: Completion<T>,
+    scope: Vec<String>,
+    /// The tokio runtime the REPL runs on, if any.
+    #[cfg(feature = "tokio")]
+    runtime: Option<tokio::runtime::Handle>,
+}
+
+impl<T: Run + Parser> ReplHelper<T> {
+    /// Runs a completion function while the REPL is blocked on reading the
+    /// line: on the REPL's tokio runtime if there is one, or on the built-in
+    /// executor otherwise.
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        #[cfg(feature = "tokio")]
+        if let Some(runtime) = &self.runtime {
+            return runtime.block_on(future);
+        }
+        block_on(future)
+    }
+}
+
+impl<T: Run + Parser> Completer for ReplHelper<T> {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(complete(&self.scope, &line[..pos], |words| {
+            self.block_on(
+                self.completion
+                    .complete(std::iter::once(String::new()).chain(words)),
+            )
+        }))
+    }
+}
+
+impl<T> Hinter for ReplHelper<T> {
+    type Hint = String;
+}
+
+impl<T> Highlighter for ReplHelper<T> {}
+
+impl<T> Validator for ReplHelper<T> {}
+
+impl<T: Run + Parser> Helper for ReplHelper<T> {}
+
+/// Returns the start position of the word under the cursor and the
+/// candidates to complete it with. `line` is relative to the `scope`
+/// subcommands. `candidates` completes the last of the words it receives,
+/// which start with the `scope`.
+pub(crate) fn complete(
+    scope: &[String],
+    line: &str,
+    candidates: impl FnOnce(Vec<String>) -> Vec<String>,
+) -> (usize, Vec<String>) {
+    let start = line
+        .rfind(char::is_whitespace)
+        .map(|idx| idx + 1)
+        .unwrap_or(0);
+    let current = &line[start..];
+    let words = shlex::split(&line[..start]).unwrap_or_else(|| {
+        line[..start]
+            .split_whitespace()
+            .map(str::to_owned)
+            .collect()
+    });
+
+    let line_is_empty = words.is_empty();
+    let words: Vec<String> = scope
+        .iter()
+        .cloned()
+        .chain(words)
+        .chain(std::iter::once(current.to_owned()))
+        .collect();
+    let mut candidates = candidates(words);
+    if line_is_empty && !current.starts_with('-') {
+        candidates.extend(BUILTINS.iter().map(|name| name.to_string()));
+        if !scope.is_empty() {
+            candidates.push(PARENT_SCOPE.to_owned());
+        }
+    }
+    candidates.retain(|candidate| candidate.starts_with(current));
+    candidates.sort();
+    candidates.dedup();
+    (start, candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::{Args, Subcommand, ValueEnum};
+
+    use super::*;
+    use crate::complete::locate;
+
+    #[derive(Parser, Debug)]
+    #[command(name = "app")]
+    struct App {
+        #[arg(long, global = true)]
+        verbose: bool,
+        #[command(subcommand)]
+        cmd: Commands,
+    }
+
+    #[derive(Subcommand, Debug)]
+    enum Commands {
+        Add(AddArgs),
+        Apply,
+        #[command(hide = true)]
+        Secret,
+    }
+
+    #[derive(Args, Debug)]
+    struct AddArgs {
+        #[arg(long, short)]
+        color: Option<Color>,
+        #[arg(long)]
+        dry: bool,
+    }
+
+    #[derive(ValueEnum, Clone, Debug)]
+    enum Color {
+        Red,
+        Green,
+    }
+
+    fn complete_app(line: &str) -> (usize, Vec<String>) {
+        complete_in_scope(&[], line)
+    }
+
+    fn complete_in_scope(scope: &[&str], line: &str) -> (usize, Vec<String>) {
+        let mut command = <App as clap::CommandFactory>::command();
+        command.build();
+        let scope: Vec<String> = scope.iter().map(|s| s.to_string()).collect();
+        complete(&scope, line, |mut words| {
+            let current = words.pop().unwrap_or_default();
+            locate(&command, &words, &current).static_candidates()
+        })
+    }
+
+    #[test]
+    fn test_complete() {
+        assert_eq!(
+            (0, vec!["add".to_owned(), "apply".to_owned()]),
+            complete_app("a")
+        );
+        assert_eq!(
+            (0, vec!["exit".to_owned()]),
+            complete_app("e"),
+            "builtins are completed"
+        );
+        assert_eq!((4, vec!["--color".to_owned()]), complete_app("add --co"));
+        assert_eq!(
+            (4, vec!["--verbose".to_owned()]),
+            complete_app("add --v"),
+            "global arguments are completed in subcommands"
+        );
+        assert_eq!(
+            (12, vec!["green".to_owned(), "red".to_owned()]),
+            complete_app("add --color ")
+        );
+        assert_eq!(
+            (10, vec!["green".to_owned()]),
+            complete_app("add -v -c gr")
+        );
+        assert_eq!(
+            (0, Vec::<String>::new()),
+            complete_app("s"),
+            "hidden subcommands are not completed"
+        );
+        assert_eq!(
+            (0, vec!["--color".to_owned()]),
+            complete_in_scope(&["add"], "--c")
+        );
+        assert_eq!(
+            (0, vec!["..".to_owned()]),
+            complete_in_scope(&["add"], "."),
+        );
+    }
+
+    #[derive(Parser, Debug)]
+    #[command(name = "app")]
+    struct Nested {
+        #[command(subcommand)]
+        cmd: NestedCommands,
+    }
+
+    #[derive(Subcommand, Debug)]
+    enum NestedCommands {
+        #[command(subcommand)]
+        Calc(CalcCommands),
+        Beep,
+    }
+
+    #[derive(Subcommand, Debug)]
+    enum CalcCommands {
+        Add { a: u32, b: u32 },
+    }
+
+    fn nested_group_path(args: &[&str]) -> Option<Vec<String>> {
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        group_path(<Nested as clap::CommandFactory>::command(), &args)
+    }
+
+    #[test]
+    fn test_group_path() {
+        assert_eq!(Some(vec![]), nested_group_path(&["app"]));
+        assert_eq!(
+            Some(vec!["calc".to_owned()]),
+            nested_group_path(&["app", "calc"])
+        );
+        assert_eq!(None, nested_group_path(&["app", "beep"]));
+        assert_eq!(None, nested_group_path(&["app", "calc", "add", "1", "2"]));
+        assert_eq!(None, nested_group_path(&["app", "calc", "add"]));
+    }
+}
@@ -0,0 +1,257 @@
+//! Record of the handlers that ran, with their durations and outcomes.
+use std::fmt::{self, Display, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use clap::Args;
+
+use crate::error::CliError;
+use crate::layer::{BoxFuture, InvocationKind, Layer, Next};
+use crate::params::Collect;
+
+/// A global `--timings` flag that prints the [ExecutionReport] of the command
+/// to stderr once it finishes.
+#[derive(Args, Debug, Clone, Default, PartialEq, Eq)]
+pub struct TimingsArgs {
+    /// Print how long every handler took
+    #[arg(long, global = true)]
+    pub timings: bool,
+}
+
+impl Collect for TimingsArgs {}
+
+/// The handlers that ran, in the order they finished. Returned by
+/// `ClingFinished::execution_report()` when the run was recorded with
+/// `Cling::record_execution()` or `--timings`.
+///
+/// Durations are wall-clock times of the handlers themselves, layers wrapping
+/// a handler are not included. Handlers skipped by a layer are not reported.
+///
+/// With the `output` feature, the report implements `serde::Serialize`, e.g.
+/// to export it as JSON and compare timings across runs. Durations are
+/// serialized as `duration_ms`, in milliseconds.
+///
+/// Example:
+/// ```rust
+/// use cling::prelude::*;
+///
+/// #[derive(Run, Parser, Debug, Clone)]
+/// #[cling(run = "connect")]
+/// pub struct App {
+///     #[command(subcommand)]
+///     pub cmd: Commands,
+/// }
+///
+/// #[derive(Run, Subcommand, Debug, Clone)]
+/// pub enum Commands {
+///     #[cling(run = "list")]
+///     List,
+/// }
+///
+/// fn connect() {}
+/// fn list() {}
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let finished = Cling::<App>::try_parse_from(["app", "list"])
+///     .unwrap()
+///     .record_execution(true)
+///     .run()
+///     .await;
+/// let report = finished.execution_report();
+/// let handlers: Vec<_> = report.handlers.iter().map(|run| run.handler).collect();
+/// assert_eq!(vec!["connect", "list"], handlers);
+/// # }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "output", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct ExecutionReport {
+    pub handlers: Vec<HandlerRun>,
+}
+
+/// A handler in an [ExecutionReport].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "output", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct HandlerRun {
+    pub kind: InvocationKind,
+    /// Path of the handler function as written in the attribute.
+    pub handler: &'static str,
+    /// Name of the [Run](crate::Run) type the handler is attached to.
+    #[cfg_attr(feature = "output", serde(rename = "type"))]
+    pub type_name: &'static str,
+    /// Depth of the command in the command tree.
+    pub level: usize,
+    #[cfg_attr(
+        feature = "output",
+        serde(rename = "duration_ms", serialize_with = "serialize_millis")
+    )]
+    pub duration: Duration,
+    #[cfg_attr(feature = "output", serde(flatten))]
+    pub outcome: HandlerOutcome,
+}
+
+/// How a [HandlerRun] ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "output",
+    derive(serde::Serialize),
+    serde(tag = "outcome", rename_all = "snake_case")
+)]
+pub enum HandlerOutcome {
+    Succeeded,
+    /// The handler returned an error, with the exit code and message of the
+    /// error.
+    Failed {
+        exit_code: u8,
+        #[cfg_attr(feature = "output", serde(rename = "error"))]
+        message: String,
+    },
+}
+
+impl HandlerOutcome {
+    fn from_result(result: &Result<(), CliError>) -> Self {
+        match result {
+            | Ok(()) => HandlerOutcome::Succeeded,
+            | Err(e) => {
+                HandlerOutcome::Failed {
+                    exit_code: e.exit_code(),
+                    message: e.to_string(),
+                }
+            }
+        }
+    }
+}
+
+impl ExecutionReport {
+    /// The sum of the durations of all handlers.
+    pub fn total(&self) -> Duration {
+        self.handlers.iter().map(|run| run.duration).sum()
+    }
+}
+
+impl Display for ExecutionReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Timings:")?;
+        for run in &self.handlers {
+            let outcome = match run.outcome {
+                | HandlerOutcome::Succeeded => "ok",
+                | HandlerOutcome::Failed { .. } => "failed",
+            };
+            // Handlers of subcommands are indented under their parents.
+            writeln!(
+                f,
+                "{:>10}  {:<6}  {}{} `{}` of `{}`",
+                format!("{:.1?}", run.duration),
+                outcome,
+                "  ".repeat(run.level),
+                run.kind,
+                run.handler,
+                run.type_name,
+            )?;
+        }
+        writeln!(f, "{:>10}  total", format!("{:.1?}", self.total()))
+    }
+}
+
+/// A built-in [Layer] that collects the [HandlerRun]s of a command while it
+/// runs. It is the innermost layer, so other layers are not timed.
+#[derive(Debug, Default)]
+pub(crate) struct ExecutionRecorder(Mutex<Vec<HandlerRun>>);
+
+impl ExecutionRecorder {
+    pub(crate) fn report(&self) -> ExecutionReport {
+        ExecutionReport {
+            handlers: self.0.lock().unwrap().clone(),
+        }
+    }
+}
+
+impl Layer for ExecutionRecorder {
+    fn call<'a>(
+        self: Arc<Self>,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<(), CliError>> {
+        let invocation = next.invocation().clone();
+        let Some(handler) = invocation.handler_name() else {
+            return next.run();
+        };
+        Box::pin(async move {
+            let started = Instant::now();
+            let result = next.run().await;
+            let run = HandlerRun {
+                kind: invocation.kind(),
+                handler,
+                type_name: invocation.type_name(),
+                level: invocation.level(),
+                duration: started.elapsed(),
+                outcome: HandlerOutcome::from_result(&result),
+            };
+            self.0.lock().unwrap().push(run);
+            result
+        })
+    }
+}
+
+#[cfg(feature = "output")]
+fn serialize_millis<S: serde::Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report() -> ExecutionReport {
+        ExecutionReport {
+            handlers: vec![
+                HandlerRun {
+                    kind: InvocationKind::Run,
+                    handler: "connect",
+                    type_name: "App",
+                    level: 0,
+                    duration: Duration::from_millis(12),
+                    outcome: HandlerOutcome::Succeeded,
+                },
+                HandlerRun {
+                    kind: InvocationKind::Run,
+                    handler: "list",
+                    type_name: "Commands::List",
+                    level: 1,
+                    duration: Duration::from_micros(1500),
+                    outcome: HandlerOutcome::Failed {
+                        exit_code: 1,
+                        message: "Error: \"nope\"".to_owned(),
+                    },
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            "Timings:\n    12.0ms  ok      run `connect` of `App`\n     \
+             1.5ms  failed    run `list` of `Commands::List`\n    13.5ms  \
+             total\n",
+            report().to_string()
+        );
+    }
+
+    #[cfg(feature = "output")]
+    #[test]
+    fn test_serialize() {
+        assert_eq!(
+            "{\"handlers\":[{\"kind\":\"run\",\"handler\":\"connect\",\"type\"\
+             :\"App\",\"level\":0,\"duration_ms\":12.0,\"outcome\":\"\
+             succeeded\"},{\"kind\":\"run\",\"handler\":\"list\",\"type\":\"\
+             Commands::List\",\"level\":1,\"duration_ms\":1.5,\"outcome\":\"\
+             failed\",\"exit_code\":1,\"error\":\"Error: \\\"nope\\\"\"}]}",
+            serde_json::to_string(&report()).unwrap()
+        );
+    }
+}
@@ -0,0 +1,62 @@
+//! Values that are current for the thread while a future is polled, e.g. the
+//! output capture of a test run.
+use std::cell::RefCell;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::thread::LocalKey;
+
+/// A thread-local slot holding the current value of type `T`, declared with
+/// `thread_local!`.
+pub(crate) type Slot<T> = LocalKey<RefCell<Option<Arc<T>>>>;
+
+/// The value that is current in `slot`, if any.
+pub(crate) fn current<T>(slot: &'static Slot<T>) -> Option<Arc<T>> {
+    slot.with(|current| current.borrow().clone())
+}
+
+/// Makes `value` current in `slot` every time `future` is polled.
+pub(crate) fn scope<T, F: Future>(
+    slot: &'static Slot<T>,
+    value: Arc<T>,
+    future: F,
+) -> Scoped<T, F> {
+    Scoped {
+        slot,
+        value,
+        future: Box::pin(future),
+    }
+}
+
+/// Future returned by [scope].
+#[doc(hidden)]
+pub struct Scoped<T: 'static, F> {
+    slot: &'static Slot<T>,
+    value: Arc<T>,
+    future: Pin<Box<F>>,
+}
+
+impl<T, F: Future> Future for Scoped<T, F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // Restores the previous value when dropped, even if polling panics.
+        struct Restore<T: 'static> {
+            slot: &'static Slot<T>,
+            previous: Option<Arc<T>>,
+        }
+        impl<T> Drop for Restore<T> {
+            fn drop(&mut self) {
+                let previous = self.previous.take();
+                self.slot.with(|current| *current.borrow_mut() = previous);
+            }
+        }
+        let value = self.value.clone();
+        let _restore = Restore {
+            slot: self.slot,
+            previous: self.slot.with(|current| current.replace(Some(value))),
+        };
+        self.future.as_mut().poll(cx)
+    }
+}
@@ -0,0 +1,109 @@
+//! Graceful shutdown on SIGINT/SIGTERM (Ctrl-C on Windows).
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Once};
+use std::time::Duration;
+
+use crate::cancellation::Cancellation;
+use crate::error::CliError;
+
+/// The run that receives the signals. The process-wide handler can only be
+/// installed once, so it dispatches to whichever run is in progress.
+static CURRENT: Mutex<Option<Arc<Shutdown>>> = Mutex::new(None);
+static INSTALL: Once = Once::new();
+
+struct Shutdown {
+    cancellation: Cancellation,
+    grace_period: Duration,
+    signals: AtomicUsize,
+    finished: Mutex<bool>,
+    finished_changed: Condvar,
+}
+
+/// Routes signals to `cancellation` until dropped.
+pub(crate) struct ShutdownGuard {
+    shutdown: Arc<Shutdown>,
+}
+
+impl ShutdownGuard {
+    pub(crate) fn install(
+        cancellation: Cancellation,
+        grace_period: Duration,
+    ) -> Self {
+        INSTALL.call_once(|| {
+            if let Err(e) = ctrlc::set_handler(on_signal) {
+                tracing::warn!(
+                    target: "cling",
+                    "Failed to install the signal handler: {}",
+                    e
+                );
+            }
+        });
+        let shutdown = Arc::new(Shutdown {
+            cancellation,
+            grace_period,
+            signals: AtomicUsize::new(0),
+            finished: Mutex::new(false),
+            finished_changed: Condvar::new(),
+        });
+        *CURRENT.lock().unwrap() = Some(shutdown.clone());
+        Self { shutdown }
+    }
+
+    /// Whether a signal was received while the guard was installed.
+    pub(crate) fn interrupted(&self) -> bool {
+        self.shutdown.signals.load(Ordering::SeqCst) > 0
+    }
+}
+
+impl Drop for ShutdownGuard {
+    fn drop(&mut self) {
+        let mut current = CURRENT.lock().unwrap();
+        if current
+            .as_ref()
+            .is_some_and(|current| Arc::ptr_eq(current, &self.shutdown))
+        {
+            *current = None;
+        }
+        *self.shutdown.finished.lock().unwrap() = true;
+        self.shutdown.finished_changed.notify_all();
+    }
+}
+
+fn on_signal() {
+    let Some(shutdown) = CURRENT.lock().unwrap().clone() else {
+        // Nothing is running, behave like the default handler.
+        exit_interrupted();
+    };
+    if shutdown.signals.fetch_add(1, Ordering::SeqCst) > 0 {
+        // A second signal terminates immediately.
+        exit_interrupted();
+    }
+    tracing::debug!(
+        target: "cling",
+        "Received shutdown signal, cancelling with a grace period of {:?}",
+        shutdown.grace_period
+    );
+    shutdown.cancellation.cancel();
+    std::thread::spawn(move || {
+        let finished = shutdown.finished.lock().unwrap();
+        let (finished, _) = shutdown
+            .finished_changed
+            .wait_timeout_while(finished, shutdown.grace_period, |finished| {
+                !*finished
+            })
+            .unwrap();
+        if !*finished {
+            let _ = writeln!(
+                crate::console::stderr(),
+                "Command did not stop within {:?}, exiting",
+                shutdown.grace_period
+            );
+            exit_interrupted();
+        }
+    });
+}
+
+fn exit_interrupted() -> ! {
+    std::process::exit(CliError::Interrupted.exit_code().into())
+}
@@ -0,0 +1,266 @@
+//! Incremental rendering of streams returned by handlers.
+use std::future::poll_fn;
+use std::io::{self, Write};
+
+use futures_core::Stream;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::effects::{_Stream, EffectContext, IntoEffect};
+use crate::error::CliError;
+use crate::output::{
+    column_widths,
+    csv_line,
+    format_row,
+    project,
+    render_cell,
+    row,
+    to_value,
+    OutputArgs,
+    OutputFormat,
+};
+
+/// Number of rows buffered to size the columns of tables.
+const TABLE_SIZING_ROWS: usize = 100;
+
+/// Handlers can return a [Stream] of serializable items, which is rendered
+/// item by item as the items arrive instead of being collected first.
+///
+/// Items are rendered according to the [OutputArgs] of the command: JSON as
+/// JSON Lines, YAML as a sequence, CSV with a header taken from the first
+/// item, and tables with columns sized by the first rows. `--format`
+/// templates and `--fields` apply to every item.
+///
+/// Example:
+/// ```rust
+/// use cling::prelude::*;
+/// use futures_util::stream::{self, Stream};
+///
+/// fn list_ids() -> impl Stream<Item = u64> + Send {
+///     stream::iter(0..10_000)
+/// }
+/// ```
+impl<S, T> IntoEffect<_Stream> for S
+where
+    S: Stream<Item = T> + Send,
+    T: Serialize + Send,
+{
+    type Effect = ();
+
+    async fn into_effect(self) -> Result<(), CliError> {
+        let args = EffectContext::with_current(|context| {
+            context
+                .map(|context| context.output.clone())
+                .unwrap_or_default()
+        });
+        let mut renderer = StreamRenderer::new(args, crate::console::stdout());
+        let mut stream = std::pin::pin!(self);
+        while let Some(item) = poll_fn(|cx| stream.as_mut().poll_next(cx)).await
+        {
+            let written = renderer.push(to_value(&item)?);
+            if let Err(e) = written {
+                return closed_pipe_ok(e);
+            }
+        }
+        renderer.finish().or_else(closed_pipe_ok)
+    }
+}
+
+/// The reader of the output going away (e.g. `| head`) is not an error.
+fn closed_pipe_ok(e: io::Error) -> Result<(), CliError> {
+    match e.kind() {
+        | io::ErrorKind::BrokenPipe => Ok(()),
+        | _ => Err(e.into()),
+    }
+}
+
+/// Writes items to `out` in the format selected by `args`.
+pub(crate) struct StreamRenderer<W> {
+    args: OutputArgs,
+    out: W,
+    // The columns of CSV and table output, taken from the first item. Empty
+    // if the first item isn't an object.
+    columns: Option<Vec<String>>,
+    // Table rows (including the header) waiting for the columns to be sized.
+    pending: Vec<Vec<String>>,
+    widths: Option<Vec<usize>>,
+}
+
+impl<W: Write> StreamRenderer<W> {
+    pub(crate) fn new(args: OutputArgs, out: W) -> Self {
+        Self {
+            args,
+            out,
+            columns: None,
+            pending: Vec::new(),
+            widths: None,
+        }
+    }
+
+    pub(crate) fn push(&mut self, item: Value) -> io::Result<()> {
+        let item = if self.args.fields.is_empty() {
+            item
+        } else {
+            project(&item, &self.args.fields)
+        };
+        if let Some(template) = &self.args.format {
+            return writeln!(self.out, "{}", template.render(&item));
+        }
+        match self.args.output {
+            // Like the text of a `Vec`, one item per line.
+            | OutputFormat::Text => {
+                writeln!(self.out, "{}", render_cell(&item))
+            }
+            | OutputFormat::Json => {
+                // Serializing a `Value` can't fail.
+                let line = serde_json::to_string(&item).unwrap_or_default();
+                writeln!(self.out, "{}", line)
+            }
+            | OutputFormat::Yaml => {
+                // Every item is an element of the same sequence.
+                let element =
+                    serde_norway::to_string(std::slice::from_ref(&item))
+                        .unwrap_or_default();
+                write!(self.out, "{}", element)
+            }
+            | OutputFormat::Csv => {
+                if let Some(header) = self.header(&item) {
+                    writeln!(self.out, "{}", csv_line(&header))?;
+                }
+                let row = self.row(&item);
+                writeln!(self.out, "{}", csv_line(&row))
+            }
+            | OutputFormat::Table => {
+                if let Some(header) = self.header(&item) {
+                    let header = header.iter().map(|c| c.to_uppercase());
+                    self.pending.push(header.collect());
+                }
+                let row = self.row(&item);
+                match &self.widths {
+                    | Some(widths) => {
+                        writeln!(self.out, "{}", format_row(&row, widths))
+                    }
+                    | None => {
+                        self.pending.push(row);
+                        if self.pending.len() > TABLE_SIZING_ROWS {
+                            self.flush_pending()?;
+                        }
+                        Ok(())
+                    }
+                }
+            }
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        self.flush_pending()?;
+        self.out.flush()
+    }
+
+    /// Sets the columns from the first item, returning the header to print.
+    fn header(&mut self, item: &Value) -> Option<Vec<String>> {
+        if self.columns.is_some() {
+            return None;
+        }
+        let columns = match item {
+            | Value::Object(fields) => fields.keys().cloned().collect(),
+            | _ => Vec::new(),
+        };
+        self.columns = Some(columns.clone());
+        Some(columns).filter(|columns| !columns.is_empty())
+    }
+
+    fn row(&self, item: &Value) -> Vec<String> {
+        match &self.columns {
+            | Some(columns) if !columns.is_empty() => row(item, columns),
+            | _ => vec![render_cell(item)],
+        }
+    }
+
+    fn flush_pending(&mut self) -> io::Result<()> {
+        if self.widths.is_some() {
+            return Ok(());
+        }
+        let widths = column_widths(&self.pending);
+        for row in self.pending.drain(..) {
+            writeln!(self.out, "{}", format_row(&row, &widths))?;
+        }
+        self.widths = Some(widths);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn render(args: OutputArgs, items: Vec<Value>) -> String {
+        let mut out = Vec::new();
+        let mut renderer = StreamRenderer::new(args, &mut out);
+        for item in items {
+            renderer.push(item).unwrap();
+        }
+        renderer.finish().unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    fn projects() -> Vec<Value> {
+        vec![
+            json!({"name": "cling", "stars": 100}),
+            json!({"name": "clap", "stars": 15000}),
+        ]
+    }
+
+    fn format(output: OutputFormat) -> OutputArgs {
+        OutputArgs {
+            output,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_render_stream() {
+        assert_eq!(
+            "{\"name\":\"cling\",\"stars\":100}\n{\"name\":\"clap\",\"stars\":\
+             15000}\n",
+            render(format(OutputFormat::Json), projects())
+        );
+        assert_eq!(
+            "- name: cling\n  stars: 100\n- name: clap\n  stars: 15000\n",
+            render(format(OutputFormat::Yaml), projects())
+        );
+        assert_eq!(
+            "name,stars\ncling,100\nclap,15000\n",
+            render(format(OutputFormat::Csv), projects())
+        );
+        assert_eq!(
+            "NAME   STARS\ncling  100\nclap   15000\n",
+            render(format(OutputFormat::Table), projects())
+        );
+        assert_eq!(
+            "1\n2\n",
+            render(format(OutputFormat::Csv), vec![json!(1), json!(2)])
+        );
+        let args = OutputArgs {
+            output: OutputFormat::Csv,
+            fields: vec!["stars".to_owned()],
+            ..Default::default()
+        };
+        assert_eq!("stars\n100\n15000\n", render(args, projects()));
+    }
+
+    #[test]
+    fn test_table_columns_are_sized_by_first_rows() {
+        let mut items: Vec<Value> = (0..TABLE_SIZING_ROWS)
+            .map(|i| json!({"id": i, "name": "a"}))
+            .collect();
+        items.push(json!({"id": "wider than the column", "name": "b"}));
+        let table = render(format(OutputFormat::Table), items);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!("ID  NAME", lines[0]);
+        assert_eq!("99  a", lines[100]);
+        assert_eq!("wider than the column  b", lines[101]);
+    }
+}
@@ -0,0 +1,187 @@
+//! `--format` templates, e.g. `{{.name}}\t{{.owner.id}}`.
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use serde_json::Value;
+
+use crate::output::render_cell;
+
+/// A template that renders a value by replacing `{{.path}}` placeholders with
+/// fields of the value.
+///
+/// Paths are dot-separated field names or array indices, e.g.
+/// `{{.owner.name}}` or `{{.tags.0}}`, and `{{.}}` is the value itself.
+/// Missing fields render as empty strings. The escapes `\t`, `\n`, and `\\`
+/// are supported outside placeholders so templates can be passed from shell
+/// scripts without quoting tricks.
+///
+/// Example:
+/// ```rust
+/// use cling::Template;
+/// use serde_json::json;
+///
+/// let template: Template = "{{.name}}\\t{{.owner.id}}".parse().unwrap();
+/// let project = json!({"name": "cling", "owner": {"id": 7}});
+/// assert_eq!("cling\t7", template.render(&project));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    source: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Field(Vec<String>),
+}
+
+impl Template {
+    /// Renders the template for `value`.
+    pub fn render(&self, value: &Value) -> String {
+        let mut out = String::new();
+        for segment in &self.segments {
+            match segment {
+                | Segment::Literal(literal) => out.push_str(literal),
+                | Segment::Field(path) => {
+                    if let Some(field) = lookup(value, path) {
+                        out.push_str(&render_cell(field));
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut segments = Vec::new();
+        let mut rest = source;
+        while !rest.is_empty() {
+            let Some(start) = rest.find("{{") else {
+                segments.push(Segment::Literal(unescape(rest)));
+                break;
+            };
+            if start > 0 {
+                segments.push(Segment::Literal(unescape(&rest[..start])));
+            }
+            let placeholder = &rest[start + 2..];
+            let Some(end) = placeholder.find("}}") else {
+                return Err(format!(
+                    "unterminated placeholder `{}`",
+                    &rest[start..]
+                ));
+            };
+            let field = placeholder[..end].trim();
+            let Some(path) = field.strip_prefix('.') else {
+                return Err(format!(
+                    "placeholder `{{{{{}}}}}` must start with `.`",
+                    field
+                ));
+            };
+            segments.push(Segment::Field(parse_path(path)));
+            rest = &placeholder[end + 2..];
+        }
+        Ok(Self {
+            source: source.to_owned(),
+            segments,
+        })
+    }
+}
+
+impl Display for Template {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Splits a dotted path like `owner.name` into its segments. An empty path
+/// refers to the value itself.
+pub(crate) fn parse_path(path: &str) -> Vec<String> {
+    path.split('.')
+        .filter(|segment| !segment.is_empty())
+        .map(str::to_owned)
+        .collect()
+}
+
+/// Looks up a field of `value` by path, indexing arrays by position.
+pub(crate) fn lookup<'a>(
+    value: &'a Value,
+    path: &[String],
+) -> Option<&'a Value> {
+    path.iter().try_fold(value, |value, segment| {
+        match value {
+            | Value::Object(fields) => fields.get(segment),
+            | Value::Array(items) => {
+                segment.parse::<usize>().ok().and_then(|i| items.get(i))
+            }
+            | _ => None,
+        }
+    })
+}
+
+fn unescape(literal: &str) -> String {
+    let mut out = String::with_capacity(literal.len());
+    let mut chars = literal.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            | Some('t') => out.push('\t'),
+            | Some('n') => out.push('\n'),
+            | Some('\\') => out.push('\\'),
+            | Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            | None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let value = json!({
+            "name": "cling",
+            "owner": {"id": 7},
+            "tags": ["cli", "clap"],
+        });
+        let render = |template: &str| {
+            template.parse::<Template>().unwrap().render(&value)
+        };
+        assert_eq!("cling", render("{{.name}}"));
+        assert_eq!("cling\t7", render("{{ .name }}\\t{{.owner.id}}"));
+        assert_eq!("clap", render("{{.tags.1}}"));
+        assert_eq!("[\"cli\",\"clap\"]", render("{{.tags}}"));
+        assert_eq!("name=", render("name={{.missing}}"));
+        assert_eq!("a\\b \\x", render("a\\\\b \\x"));
+        assert_eq!(
+            "hi",
+            "{{.}}".parse::<Template>().unwrap().render(&json!("hi"))
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            Err("unterminated placeholder `{{.name`".to_owned()),
+            "id={{.name".parse::<Template>()
+        );
+        assert_eq!(
+            Err("placeholder `{{name}}` must start with `.`".to_owned()),
+            "{{name}}".parse::<Template>()
+        );
+    }
+}
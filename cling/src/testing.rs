@@ -0,0 +1,411 @@
+//! Utilities for testing cling applications in-process.
+use std::ffi::OsString;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::{env, fs, io};
+
+use clap::Parser;
+
+/// Calls a handler with `args` like cling does when running a command, and
+/// applies its effect (e.g. a returned [State](crate::State)) to `args`.
+/// Evaluates to a future of `Result<(), CliError>`.
+///
+/// This is a macro rather than a function since handler parameters borrow
+/// `args` for a lifetime that can't be named in a function signature.
+///
+/// Example:
+/// ```rust
+/// use cling::prelude::*;
+/// use cling::testing::call_handler;
+/// use cling::CollectedArgs;
+///
+/// #[derive(Collect, Args, Debug, Clone)]
+/// pub struct Options {
+///     #[arg(long)]
+///     pub name: String,
+/// }
+///
+/// #[derive(Clone, Debug)]
+/// struct Database;
+///
+/// async fn create(
+///     State(_db): State<Database>,
+///     options: &Options,
+/// ) -> State<String> {
+///     State(format!("created {}", options.name))
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let mut args = CollectedArgs::builder()
+///     .with(&Options {
+///         name: "cling".to_owned(),
+///     })
+///     .with_state(Database)
+///     .build();
+/// call_handler!(create, &mut args).await.unwrap();
+/// assert_eq!(
+///     Some(&State("created cling".to_owned())),
+///     args.get::<State<String>>()
+/// );
+/// # }
+/// ```
+#[doc(inline)]
+pub use crate::__call_handler as call_handler;
+use crate::app::{Cling, ClingFinished, Settings};
+use crate::console::Capture;
+use crate::environment::Environment;
+use crate::error::format_clap_error;
+use crate::layer::Layer;
+use crate::params::{CollectedArgs, InsertState};
+use crate::Run;
+
+// Exported at the crate root by `#[macro_export]`, documented as
+// `testing::call_handler`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __call_handler {
+    ($handler:expr, $args:expr $(,)?) => {{
+        let args: &mut $crate::CollectedArgs = $args;
+        async move {
+            let context = $crate::_private::EffectContext::capture(args);
+            let effect = {
+                let args = &mut *args;
+                context
+                    .scope(async move {
+                        $crate::IntoEffect::into_effect(
+                            $crate::_private::Handler::call($handler, args)?,
+                        )
+                        .await
+                    })
+                    .await?
+            };
+            #[allow(unused_imports)]
+            use $crate::_private::{
+                DebuggableState as _,
+                UnknownDebugState as _,
+            };
+            let debug =
+                (&$crate::_private::DebugProbe::of(&effect)).state_debug_fn();
+            $crate::_private::HandlerEffect::apply_effect_with_debug(
+                effect, args, debug,
+            );
+            ::std::result::Result::<(), $crate::CliError>::Ok(())
+        }
+    }};
+}
+
+/// Runs a cling application in-process against injected states and captures
+/// its output.
+///
+/// Everything written to [cling::stdout()](crate::stdout) and
+/// [cling::stderr()](crate::stderr) while the command runs is captured. cling
+/// writes all of its own output there, including errors,
+/// [Output](crate::Output) effects, and dry-run plans.
+///
+/// Handlers must write to `cling::stdout()` and `cling::stderr()` for their
+/// output to be captured. `println!` and `eprintln!` write to the process
+/// directly and are never captured.
+///
+/// Example:
+/// ```rust
+/// use std::io::Write;
+///
+/// use cling::prelude::*;
+/// use cling::testing::TestRunner;
+///
+/// #[derive(Run, Collect, Parser, Debug, Clone)]
+/// #[cling(run = "greet")]
+/// pub struct App {
+///     name: String,
+/// }
+///
+/// #[derive(Clone, Debug)]
+/// struct Greeting(String);
+///
+/// fn greet(
+///     State(greeting): State<Greeting>,
+///     app: &App,
+/// ) -> std::io::Result<()> {
+///     writeln!(cling::stdout(), "{}, {}!", greeting.0, app.name)
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let runner = TestRunner::<App>::new().with_state(Greeting("Hi".into()));
+/// let outcome = runner.run(["app", "Ferris"]).await;
+/// assert_eq!(0, outcome.exit_code);
+/// assert_eq!("Hi, Ferris!\n", outcome.stdout);
+///
+/// let outcome = runner.run(["app"]).await;
+/// assert_eq!(2, outcome.exit_code);
+/// assert!(outcome.stderr.contains("required arguments were not provided"));
+/// # }
+/// ```
+pub struct TestRunner<T> {
+    settings: Settings,
+    states: Vec<InsertState>,
+    sandbox: Option<Sandbox>,
+    _parsed: PhantomData<fn() -> T>,
+}
+
+/// The result of a command run by a [TestRunner].
+#[non_exhaustive]
+pub struct TestOutcome {
+    /// The exit code the process would have exited with.
+    pub exit_code: u8,
+    pub stdout: String,
+    pub stderr: String,
+    /// The arguments and states collected by the end of the run. Empty if the
+    /// command line failed to parse.
+    pub collected: CollectedArgs,
+}
+
+impl TestOutcome {
+    pub fn is_success(&self) -> bool {
+        self.exit_code == 0
+    }
+}
+
+impl<T: Run + Parser> Default for TestRunner<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Run + Parser> TestRunner<T> {
+    pub fn new() -> Self {
+        Self {
+            settings: Settings::default(),
+            states: Vec::new(),
+            sandbox: None,
+            _parsed: PhantomData,
+        }
+    }
+
+    /// Injects a state that handlers can extract with [State](crate::State).
+    /// Every run starts with a clone of it.
+    pub fn with_state<S>(mut self, state: S) -> Self
+    where
+        S: Clone + Send + Sync + std::fmt::Debug + 'static,
+    {
+        self.states.push(Arc::new(move |args: &mut CollectedArgs| {
+            args.insert_debug_state(state.clone())
+        }));
+        self
+    }
+
+    /// Adds a layer, like `Cling::layer()`.
+    pub fn layer(mut self, layer: impl Layer) -> Self {
+        self.settings.layers.push(Arc::new(layer));
+        self
+    }
+
+    /// Runs every command in `sandbox`.
+    pub fn sandbox(mut self, sandbox: Sandbox) -> Self {
+        self.sandbox = Some(sandbox);
+        self
+    }
+
+    /// Parses `args`, which include the binary name, runs the command and
+    /// reports the result like a `main()` returning
+    /// [ClingFinished](crate::ClingFinished) would.
+    pub async fn run<I, B>(&self, args: I) -> TestOutcome
+    where
+        I: IntoIterator<Item = B>,
+        B: Into<OsString> + Clone,
+    {
+        let environment = self
+            .sandbox
+            .as_ref()
+            .map(Sandbox::environment)
+            .unwrap_or_default();
+        let stdin = self
+            .sandbox
+            .as_ref()
+            .map(|sandbox| sandbox.stdin.clone())
+            .unwrap_or_default();
+        let capture = Arc::new(Capture::with_stdin(stdin));
+        let (exit_code, collected) = capture
+            .scope(async {
+                let finished: ClingFinished<T> = match environment
+                    .parse::<T, _, _>(args)
+                    .map_err(format_clap_error::<T>)
+                {
+                    | Ok(parsed) => {
+                        let mut collected = CollectedArgs::new();
+                        collected.set_environment(environment.clone());
+                        for insert_state in &self.states {
+                            insert_state(&mut collected);
+                        }
+                        Cling::with_settings(
+                            parsed,
+                            self.settings.clone(),
+                            collected,
+                        )
+                        .run()
+                        .await
+                    }
+                    | Err(e) => Cling::failed(e),
+                };
+                finished.print_report()
+            })
+            .await;
+        TestOutcome {
+            exit_code,
+            stdout: capture.stdout(),
+            stderr: capture.stderr(),
+            collected,
+        }
+    }
+}
+
+/// An isolated environment for the commands of a [TestRunner], in the spirit
+/// of Click's `CliRunner.isolated_filesystem()`.
+///
+/// A sandbox owns a fresh temporary directory, which is the working directory
+/// of the commands unless changed with [Sandbox::current_dir], and is removed
+/// once the sandbox and all its clones are dropped.
+///
+/// The environment variables and the working directory of the process are
+/// never changed. Instead, the sandbox replaces them for the `env` arguments
+/// of the command and for handlers that read them through the
+/// [Environment] extractor, so sandboxed runs can run in parallel with each
+/// other and with the rest of the tests. Handlers that use `std::env` or pass
+/// relative paths to `std::fs` see the process environment.
+///
+/// Example:
+/// ```rust
+/// use std::io::{Read, Write};
+///
+/// use cling::prelude::*;
+/// use cling::testing::{Sandbox, TestRunner};
+///
+/// #[derive(Run, Collect, Parser, Debug, Clone)]
+/// #[cling(run = "save")]
+/// pub struct App {
+///     #[arg(long, env = "APP_FILE")]
+///     file: String,
+/// }
+///
+/// fn save(env: Environment, app: &App) -> Result<(), CliError> {
+///     let mut input = String::new();
+///     cling::stdin().read_to_string(&mut input)?;
+///     std::fs::write(env.path(&app.file), input)?;
+///     Ok(())
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let sandbox = Sandbox::new()
+///     .unwrap()
+///     .env("APP_FILE", "notes.txt")
+///     .stdin("remember the milk");
+/// let runner = TestRunner::<App>::new().sandbox(sandbox.clone());
+/// assert!(runner.run(["app"]).await.is_success());
+///
+/// let notes = std::fs::read_to_string(sandbox.path().join("notes.txt"));
+/// assert_eq!("remember the milk", notes.unwrap());
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Sandbox {
+    dir: Arc<TempDir>,
+    current_dir: PathBuf,
+    // `None` removes the variable.
+    env: Vec<(OsString, Option<OsString>)>,
+    stdin: Vec<u8>,
+}
+
+impl Sandbox {
+    /// Creates a sandbox with an empty temporary directory.
+    pub fn new() -> io::Result<Self> {
+        let dir = TempDir::new()?;
+        Ok(Self {
+            current_dir: dir.0.clone(),
+            dir: Arc::new(dir),
+            env: Vec::new(),
+            stdin: Vec::new(),
+        })
+    }
+
+    /// The temporary directory of the sandbox.
+    pub fn path(&self) -> &Path {
+        &self.dir.0
+    }
+
+    /// Sets an environment variable.
+    pub fn env(
+        mut self,
+        key: impl Into<OsString>,
+        value: impl Into<OsString>,
+    ) -> Self {
+        self.env.push((key.into(), Some(value.into())));
+        self
+    }
+
+    /// Removes an environment variable, e.g. to hide the developer's own
+    /// configuration from the command.
+    pub fn env_remove(mut self, key: impl Into<OsString>) -> Self {
+        self.env.push((key.into(), None));
+        self
+    }
+
+    /// Sets the working directory. Relative paths are relative to the
+    /// temporary directory.
+    pub fn current_dir(mut self, dir: impl AsRef<Path>) -> Self {
+        self.current_dir = self.path().join(dir);
+        self
+    }
+
+    /// Sets the input read from [cling::stdin()](crate::stdin).
+    pub fn stdin(mut self, input: impl Into<Vec<u8>>) -> Self {
+        self.stdin = input.into();
+        self
+    }
+
+    /// The environment commands run in.
+    fn environment(&self) -> Environment {
+        Environment::overlay(self.env.clone(), self.current_dir.clone())
+    }
+}
+
+/// A temporary directory that is removed when dropped.
+#[derive(Debug)]
+struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates a directory that didn't exist before, so that it's never
+    /// shared with another sandbox or left over by an earlier process.
+    fn new() -> io::Result<Self> {
+        const ATTEMPTS: usize = 100;
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        for _ in 0..ATTEMPTS {
+            let name = format!(
+                "cling-sandbox-{}-{}",
+                std::process::id(),
+                NEXT_ID.fetch_add(1, Ordering::Relaxed)
+            );
+            let path = env::temp_dir().join(name);
+            match fs::create_dir(&path) {
+                // Matches what commands see as their working directory, e.g.
+                // on macOS where the temporary directory is behind a symlink.
+                | Ok(()) => return Ok(Self(fs::canonicalize(path)?)),
+                | Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                | Err(e) => return Err(e),
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "failed to create a unique sandbox directory",
+        ))
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,205 @@
+//! Deadlines of handlers marked with `#[cling(timeout = "...")]`.
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Condvar, Mutex, OnceLock};
+use std::task::{Context, Poll, Waker};
+use std::time::{Duration, Instant};
+
+use crate::error::CliError;
+use crate::layer::BoxFuture;
+
+/// Source of the delays used to enforce `#[cling(timeout = "...")]`.
+///
+/// Cling doesn't depend on an async runtime, so the default [ThreadTimer]
+/// wakes handlers from a background thread. Applications can use the timer of
+/// their runtime instead by passing it to `Cling::timer()`.
+///
+/// A handler can only be stopped at its `.await` points: the timeout of a
+/// sync handler, or of an async handler that blocks the thread, is only
+/// noticed once the handler returns, and its result is then discarded.
+///
+/// Example:
+/// ```rust
+/// use std::time::Duration;
+///
+/// use cling::{BoxFuture, Timer};
+///
+/// struct TokioTimer;
+///
+/// impl Timer for TokioTimer {
+///     fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+///         Box::pin(tokio::time::sleep(duration))
+///     }
+/// }
+/// ```
+pub trait Timer: Send + Sync + 'static {
+    /// Returns a future that completes after `duration`.
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()>;
+}
+
+/// A [Timer] that wakes the sleeping futures of the whole process from a
+/// single background thread, started on first use.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadTimer;
+
+impl Timer for ThreadTimer {
+    fn sleep(&self, duration: Duration) -> BoxFuture<'static, ()> {
+        let state = Arc::new(Mutex::new(SleepState::default()));
+        TimerThread::get().schedule(Instant::now() + duration, state.clone());
+        Box::pin(ThreadSleep { state })
+    }
+}
+
+#[derive(Default)]
+struct SleepState {
+    elapsed: bool,
+    waker: Option<Waker>,
+}
+
+struct ThreadSleep {
+    state: Arc<Mutex<SleepState>>,
+}
+
+impl Future for ThreadSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.elapsed {
+            return Poll::Ready(());
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// The pending sleeps of [ThreadTimer], woken by a background thread when
+/// their deadline passes.
+struct TimerThread {
+    deadlines: Mutex<BinaryHeap<Deadline>>,
+    changed: Condvar,
+}
+
+/// Ordered so that the earliest deadline is at the top of the heap.
+struct Deadline {
+    at: Instant,
+    sleep: Arc<Mutex<SleepState>>,
+}
+
+impl PartialEq for Deadline {
+    fn eq(&self, other: &Self) -> bool {
+        self.at == other.at
+    }
+}
+
+impl Eq for Deadline {}
+
+impl PartialOrd for Deadline {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Deadline {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.at.cmp(&self.at)
+    }
+}
+
+impl TimerThread {
+    fn get() -> &'static TimerThread {
+        static TIMER: OnceLock<TimerThread> = OnceLock::new();
+        TIMER.get_or_init(|| {
+            std::thread::Builder::new()
+                .name("cling-timer".to_owned())
+                .spawn(|| TimerThread::get().run())
+                .expect("failed to spawn the timer thread");
+            TimerThread {
+                deadlines: Mutex::default(),
+                changed: Condvar::new(),
+            }
+        })
+    }
+
+    fn schedule(&self, at: Instant, sleep: Arc<Mutex<SleepState>>) {
+        self.deadlines.lock().unwrap().push(Deadline { at, sleep });
+        self.changed.notify_one();
+    }
+
+    /// Wakes the sleeps whose deadline passed, and waits for the next
+    /// deadline or for a new sleep otherwise. Sleeps that were dropped (e.g.
+    /// since the handler completed first) are discarded at their deadline.
+    fn run(&self) {
+        let mut deadlines = self.deadlines.lock().unwrap();
+        loop {
+            let now = Instant::now();
+            let next = deadlines.peek().map(|deadline| deadline.at);
+            deadlines = match next {
+                | None => self.changed.wait(deadlines).unwrap(),
+                | Some(at) if at > now => {
+                    self.changed.wait_timeout(deadlines, at - now).unwrap().0
+                }
+                | Some(_) => {
+                    let deadline = deadlines.pop();
+                    // Woken tasks may schedule new sleeps right away.
+                    drop(deadlines);
+                    if let Some(deadline) = deadline {
+                        let mut sleep = deadline.sleep.lock().unwrap();
+                        sleep.elapsed = true;
+                        if let Some(waker) = sleep.waker.take() {
+                            waker.wake();
+                        }
+                    }
+                    self.deadlines.lock().unwrap()
+                }
+            };
+        }
+    }
+}
+
+/// Runs `future`, failing with [CliError::TimedOut] if it doesn't complete
+/// within `duration`.
+#[doc(hidden)]
+pub async fn timeout<'a, T>(
+    timer: Option<Arc<dyn Timer>>,
+    duration: Duration,
+    handler: &'static str,
+    future: impl Future<Output = Result<T, CliError>> + Send + 'a,
+) -> Result<T, CliError> {
+    let sleep = match timer {
+        | Some(timer) => timer.sleep(duration),
+        | None => ThreadTimer.sleep(duration),
+    };
+    Race {
+        future: Box::pin(future),
+        sleep,
+    }
+    .await
+    .unwrap_or_else(|| Err(CliError::TimedOut(handler.to_owned(), duration)))
+}
+
+/// Completes with the output of `future`, or `None` if `sleep` completes
+/// first.
+struct Race<'a, T> {
+    future: BoxFuture<'a, T>,
+    sleep: BoxFuture<'static, ()>,
+}
+
+impl<T> Future for Race<'_, T> {
+    type Output = Option<T>;
+
+    fn poll(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Self::Output> {
+        if let Poll::Ready(output) = self.future.as_mut().poll(cx) {
+            return Poll::Ready(Some(output));
+        }
+        if self.sleep.as_mut().poll(cx).is_ready() {
+            return Poll::Ready(None);
+        }
+        Poll::Pending
+    }
+}
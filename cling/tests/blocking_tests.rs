@@ -0,0 +1,63 @@
+use cling::prelude::*;
+
+#[derive(Run, Parser, Debug, Clone)]
+#[cling(run = "init")]
+struct App {
+    #[command(subcommand)]
+    cmd: Commands,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum Commands {
+    #[cling(run = "count")]
+    Count,
+    #[cling(run = "slow", timeout = "20ms")]
+    Slow,
+}
+
+fn init() -> State<u32> {
+    State(1)
+}
+
+fn count(State(n): State<u32>) -> State<u32> {
+    State(n + 1)
+}
+
+async fn slow() {
+    std::future::pending::<()>().await;
+}
+
+#[test]
+fn run_blocking() {
+    let finished = Cling::<App>::try_parse_from(["app", "count"])
+        .unwrap()
+        .run_blocking();
+    assert!(finished.is_success());
+    assert_eq!(
+        Some(&State(2)),
+        finished.collected_parameters().get::<State<u32>>()
+    );
+}
+
+#[test]
+fn timeouts_dont_need_a_runtime() {
+    let finished = Cling::<App>::try_parse_from(["app", "slow"])
+        .unwrap()
+        .run_blocking();
+    assert_eq!(124, finished.result().unwrap_err().exit_code());
+}
+
+#[cling::main]
+async fn entry() -> u32 {
+    let finished = Cling::<App>::try_parse_from(["app", "count"])
+        .unwrap()
+        .run()
+        .await;
+    let collected = finished.collected_parameters();
+    collected.get::<State<u32>>().unwrap().0
+}
+
+#[test]
+fn cling_main() {
+    assert_eq!(2, entry());
+}
@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use cling::prelude::*;
+
+#[derive(Run, Parser, Debug, Clone)]
+#[cling(run = "check")]
+struct App {
+    #[command(subcommand)]
+    cmd: Commands,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum Commands {
+    #[cling(run = "stop")]
+    Stop,
+    #[cling(run = "wait_for_signal")]
+    Wait,
+}
+
+fn check(cancellation: Cancellation) {
+    assert!(!cancellation.is_cancelled());
+}
+
+async fn stop(cancellation: Cancellation) -> Result<(), CliError> {
+    cancellation.cancel();
+    // Resolves immediately once cancelled.
+    cancellation.cancelled().await;
+    assert!(cancellation.is_cancelled());
+    Ok(())
+}
+
+async fn wait_for_signal(cancellation: Cancellation) {
+    #[cfg(unix)]
+    {
+        let status = std::process::Command::new("kill")
+            .args(["-INT", &std::process::id().to_string()])
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+    cancellation.cancelled().await;
+}
+
+#[tokio::test]
+async fn handlers_can_cancel() {
+    let finished = Cling::<App>::try_parse_from(["app", "stop"])
+        .unwrap()
+        .run()
+        .await;
+    assert!(finished.is_success());
+}
+
+#[tokio::test]
+async fn cancelled_wakes_waiting_tasks() {
+    let cancellation = Cancellation::new();
+    let waiter = tokio::spawn({
+        let cancellation = cancellation.clone();
+        async move { cancellation.cancelled().await }
+    });
+    tokio::task::yield_now().await;
+    assert!(!waiter.is_finished());
+    cancellation.cancel();
+    tokio::time::timeout(Duration::from_secs(5), waiter)
+        .await
+        .expect("waiter was not woken up")
+        .unwrap();
+}
+
+#[cfg(all(unix, feature = "signals"))]
+#[tokio::test]
+async fn signals_cancel_the_command() {
+    let finished = Cling::<App>::try_parse_from(["app", "wait"])
+        .unwrap()
+        .graceful_shutdown(Duration::from_secs(30))
+        .run()
+        .await;
+    let error = finished.result().unwrap_err();
+    assert!(matches!(error, CliError::Interrupted));
+    assert_eq!(130, error.exit_code());
+}
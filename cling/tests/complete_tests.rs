@@ -0,0 +1,177 @@
+#![cfg(feature = "completions")]
+use std::sync::{Arc, Mutex};
+
+use cling::prelude::*;
+
+#[derive(Run, Collect, Parser, Debug, Clone)]
+#[command(name = "app")]
+#[cling(run = "init")]
+struct App {
+    #[arg(long, global = true)]
+    #[cling(complete = "profiles")]
+    profile: Option<String>,
+    #[command(subcommand)]
+    cmd: Commands,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum Commands {
+    #[command(subcommand)]
+    Projects(ProjectCommands),
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum ProjectCommands {
+    List(List),
+    Move(Move),
+}
+
+#[derive(Run, Collect, Args, Debug, Clone)]
+#[cling(run = "list")]
+struct List {
+    #[arg(long)]
+    #[cling(complete = "project_names")]
+    name: Option<String>,
+    #[arg(long, value_parser = ["name", "size"])]
+    sort: Option<String>,
+}
+
+#[derive(Run, Collect, Args, Debug, Clone)]
+#[cling(run = "move_project")]
+struct Move {
+    #[cling(complete = "project_names")]
+    from: String,
+    #[cling(complete = "destinations")]
+    to: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Db {
+    projects: Vec<&'static str>,
+    calls: Arc<Mutex<Vec<String>>>,
+}
+
+impl Db {
+    fn record(&self, event: impl Into<String>) {
+        self.calls.lock().unwrap().push(event.into());
+    }
+}
+
+fn init(State(db): State<Db>) {
+    db.record("init");
+}
+
+fn list(State(db): State<Db>) {
+    db.record("list");
+}
+
+fn move_project(State(db): State<Db>) {
+    db.record("move");
+}
+
+fn profiles() -> Candidates {
+    ["dev", "prod"].into_iter().collect()
+}
+
+async fn project_names(
+    State(db): State<Db>,
+    app: &App,
+) -> Result<Candidates, CliError> {
+    db.record("project_names");
+    let prefix = app.profile.clone().unwrap_or_default();
+    Ok(db
+        .projects
+        .iter()
+        .map(|name| format!("{prefix}{name}"))
+        .collect())
+}
+
+// Completion functions see already parsed arguments.
+fn destinations(args: &Move) -> Candidates {
+    vec![format!("{}-copy", args.from)].into()
+}
+
+async fn complete(db: &Db, words: &[&str]) -> Vec<String> {
+    Cling::<App>::completion()
+        .with_state(db.clone())
+        .complete(words.iter().copied())
+        .await
+}
+
+fn db() -> Db {
+    Db {
+        projects: vec!["cling", "clap", "tokio"],
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn completes_values_with_functions() {
+    let db = db();
+    assert_eq!(
+        vec!["clap", "cling"],
+        complete(&db, &["app", "projects", "list", "--name", "cl"]).await
+    );
+    assert_eq!(
+        vec!["--name=clap", "--name=cling"],
+        complete(&db, &["app", "projects", "list", "--name=cl"]).await
+    );
+    // Handlers don't run while completing.
+    assert_eq!(vec!["project_names", "project_names"], db.calls());
+}
+
+#[tokio::test]
+async fn completes_positional_values() {
+    let db = db();
+    assert_eq!(
+        vec!["tokio"],
+        complete(&db, &["app", "projects", "move", "t"]).await
+    );
+    assert_eq!(
+        vec!["tokio-copy"],
+        complete(&db, &["app", "projects", "move", "tokio", ""]).await
+    );
+}
+
+#[tokio::test]
+async fn completion_functions_see_parent_arguments() {
+    let db = db();
+    assert_eq!(
+        vec!["dev/clap", "dev/cling", "dev/tokio"],
+        complete(
+            &db,
+            &["app", "--profile", "dev/", "projects", "list", "--name", ""]
+        )
+        .await
+    );
+    // Global arguments are completed at any level.
+    assert_eq!(
+        vec!["dev", "prod"],
+        complete(&db, &["app", "projects", "list", "--profile", ""]).await
+    );
+}
+
+#[tokio::test]
+async fn falls_back_to_static_candidates() {
+    let db = db();
+    assert_eq!(
+        vec!["help", "list", "move"],
+        complete(&db, &["app", "projects", ""]).await
+    );
+    assert_eq!(
+        vec!["--help", "--name", "--profile", "--sort"],
+        complete(&db, &["app", "projects", "list", "-"]).await
+    );
+    assert_eq!(
+        vec!["name", "size"],
+        complete(&db, &["app", "projects", "list", "--sort", ""]).await
+    );
+    // A required subcommand is missing, completion functions cannot run.
+    assert!(complete(&db, &["app", "--profile", ""]).await.is_empty());
+}
+
+impl Db {
+    fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,80 @@
+#![cfg(feature = "completions")]
+use cling::prelude::*;
+use cling::testing::TestRunner;
+use cling::{print_completions, Shell, ShellCompletions};
+
+#[derive(Run, Parser, Debug, Clone)]
+#[command(name = "app")]
+struct App {
+    #[command(subcommand)]
+    cmd: Commands,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum Commands {
+    #[cling(run = "beep")]
+    Beep,
+    #[command(hide = true)]
+    Completions(Completions),
+}
+
+#[derive(Run, Collect, Args, Debug, Clone)]
+#[cling(run = "print_completions::<App>")]
+struct Completions {
+    #[command(flatten)]
+    completions: ShellCompletions,
+}
+
+fn beep() {}
+
+#[test]
+fn test_generate_completions() {
+    for shell in [
+        Shell::Bash,
+        Shell::Zsh,
+        Shell::Fish,
+        Shell::Elvish,
+        Shell::PowerShell,
+    ] {
+        let mut script = Vec::new();
+        Cling::<App>::generate_completions(shell, &mut script);
+        let script = String::from_utf8(script).unwrap();
+        match shell {
+            // The binary computes the candidates.
+            | Shell::Bash | Shell::Zsh | Shell::Fish => {
+                assert!(script.contains("CLING_COMPLETE="), "{shell}");
+                assert!(script.contains(" app"), "{shell} script misses app");
+            }
+            | _ => {
+                assert!(script.contains("beep"), "{shell} script misses beep")
+            }
+        }
+    }
+}
+
+#[test]
+fn test_completions_subcommand_parses() {
+    let app = App::try_parse_from(["app", "completions", "fish"]).unwrap();
+    let Commands::Completions(completions) = app.cmd else {
+        panic!("expected completions subcommand");
+    };
+    assert_eq!(Shell::Fish, completions.completions.shell);
+}
+
+#[tokio::test]
+async fn test_completions_subcommand_runs() {
+    let finished = Cling::<App>::try_parse_from(["app", "completions", "bash"])
+        .unwrap()
+        .run()
+        .await;
+    assert!(finished.is_success());
+}
+
+#[tokio::test]
+async fn test_completions_subcommand_output_is_captured() {
+    let outcome = TestRunner::<App>::new()
+        .run(["app", "completions", "bash"])
+        .await;
+    assert_eq!(0, outcome.exit_code);
+    assert!(outcome.stdout.contains("CLING_COMPLETE="));
+}
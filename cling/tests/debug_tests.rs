@@ -0,0 +1,78 @@
+use cling::prelude::*;
+use cling::testing::{Sandbox, TestRunner};
+
+#[derive(Run, Parser, Collect, Debug, Clone)]
+#[cling(run = "init")]
+#[command(name = "app")]
+struct App {
+    #[arg(long)]
+    name: String,
+    #[cling(collect)]
+    #[arg(long, default_value_t = 3)]
+    retries: u32,
+    #[command(subcommand)]
+    cmd: Commands,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum Commands {
+    #[cling(run = "list")]
+    List,
+}
+
+fn init() -> State<u32> {
+    State(7)
+}
+
+fn list() {}
+
+#[tokio::test]
+async fn cling_debug_prints_collected_values() {
+    let sandbox = Sandbox::new().unwrap().env("CLING_DEBUG", "collected");
+    let outcome = TestRunner::<App>::new()
+        .sandbox(sandbox)
+        .run(["app", "--name", "x", "list"])
+        .await;
+    assert!(outcome.is_success());
+    let app = "debug_tests::App = App { name: \"x\", retries: 3, cmd: List }";
+    assert_eq!(
+        format!(
+            "Collected before run `init` of `App`:\n  \
+             cling::extractors::Collected<u32> = Collected(3)\n  \
+             {app}\nCollected before run `list` of `Commands::List`:\n  \
+             cling::extractors::Collected<u32> = Collected(3)\n  \
+             cling::extractors::State<u32> = State(7)\n  {app}\n"
+        ),
+        outcome.stderr
+    );
+}
+
+#[derive(Clone, Debug)]
+#[allow(dead_code)]
+struct Config {
+    profile: &'static str,
+}
+
+#[tokio::test]
+async fn cling_debug_prints_injected_states() {
+    let sandbox = Sandbox::new().unwrap().env("CLING_DEBUG", "collected");
+    let outcome = TestRunner::<App>::new()
+        .with_state(Config { profile: "ci" })
+        .sandbox(sandbox)
+        .run(["app", "--name", "x", "list"])
+        .await;
+    assert!(outcome.is_success());
+    assert!(outcome.stderr.contains(
+        "cling::extractors::State<debug_tests::Config> = State(Config { \
+         profile: \"ci\" })"
+    ));
+}
+
+#[tokio::test]
+async fn collected_values_are_not_printed_by_default() {
+    let outcome = TestRunner::<App>::new()
+        .run(["app", "--name", "x", "list"])
+        .await;
+    assert!(outcome.is_success());
+    assert_eq!("", outcome.stderr);
+}
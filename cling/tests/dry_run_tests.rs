@@ -0,0 +1,83 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use cling::prelude::*;
+
+static DELETED: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Run, Parser, Debug, Clone)]
+struct App {
+    #[command(flatten)]
+    dry_run: DryRunArgs,
+    #[command(subcommand)]
+    cmd: Commands,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum Commands {
+    #[cling(run = "delete")]
+    Delete,
+    #[cling(run = "sync")]
+    Sync,
+}
+
+fn delete(dry_run: DryRun) -> Result<(), CliError> {
+    for project in ["a", "b"] {
+        dry_run
+            .mutation(format!("Delete project {}", project))
+            .run(|| DELETED.fetch_add(1, Ordering::SeqCst));
+    }
+    Ok(())
+}
+
+async fn sync(dry_run: DryRun) -> State<Option<String>> {
+    let synced = dry_run
+        .mutation("Sync projects")
+        .run_async(async { "synced".to_owned() })
+        .await;
+    State(synced)
+}
+
+#[tokio::test]
+async fn dry_run_records_mutations() {
+    let finished = Cling::<App>::try_parse_from(["app", "delete", "--dry-run"])
+        .unwrap()
+        .run()
+        .await;
+    assert!(finished.is_success());
+    assert!(finished.dry_run().is_enabled());
+    assert_eq!(
+        vec!["Delete project a", "Delete project b"],
+        finished.dry_run().plan()
+    );
+
+    let finished = Cling::<App>::try_parse_from(["app", "sync"])
+        .unwrap()
+        .dry_run(true)
+        .run()
+        .await;
+    assert_eq!(vec!["Sync projects"], finished.dry_run().plan());
+    assert_eq!(
+        Some(&State(None)),
+        finished
+            .collected_parameters()
+            .get::<State<Option<String>>>()
+    );
+    // Nothing was deleted in dry-run mode.
+    assert_eq!(0, DELETED.load(Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn mutations_run_without_dry_run() {
+    let finished = Cling::<App>::try_parse_from(["app", "sync"])
+        .unwrap()
+        .run()
+        .await;
+    assert!(!finished.dry_run().is_enabled());
+    assert!(finished.dry_run().plan().is_empty());
+    assert_eq!(
+        Some(&State(Some("synced".to_owned()))),
+        finished
+            .collected_parameters()
+            .get::<State<Option<String>>>()
+    );
+}
@@ -1,6 +1,8 @@
 #[test]
 fn trycmd() {
     let t = trycmd::TestCases::new();
-    t.register_bins(trycmd::cargo::compile_examples([]).unwrap());
+    t.register_bins(
+        trycmd::cargo::compile_examples(["--features", "completions"]).unwrap(),
+    );
     t.case("../examples/cmd/*.toml").case("../README.md");
 }
@@ -116,3 +116,52 @@ async fn handler_tests() -> Result<(), CliError> {
 
     Ok(())
 }
+
+struct Admin(&'static str);
+
+impl<'a> cling::FromCollected<'a> for Admin {
+    type Rejection = CliError;
+
+    fn from_collected(args: &'a CollectedArgs) -> Result<Self, CliError> {
+        match args.get::<CommonOpts>() {
+            | Some(_) => Ok(Admin("root")),
+            | None => {
+                Err(CliError::FailedWithMessageAndCode(
+                    "Not logged in".to_owned(),
+                    77,
+                ))
+            }
+        }
+    }
+}
+
+fn admin_handler(Admin(name): Admin) -> State<&'static str> {
+    State(name)
+}
+
+fn missing_state_handler(_: State<Database>) {}
+
+#[tokio::test]
+async fn custom_extractors() {
+    let mut args = CollectedArgs::default();
+    let err = handle(&mut args, admin_handler).await.unwrap_err();
+    assert_eq!(77, err.exit_code());
+    assert_eq!("Error: Not logged in", err.to_string());
+
+    args.insert(CommonOpts, false);
+    assert_eq!(
+        SetState("root"),
+        handle(&mut args, admin_handler).await.unwrap()
+    );
+
+    // Rejections of the built-in extractors name the handler.
+    let CliError::InvalidHandler(msg) =
+        handle(&mut args, missing_state_handler).await.unwrap_err()
+    else {
+        panic!("expected an invalid handler error");
+    };
+    assert!(msg.starts_with(
+        "In `handler_tests::missing_state_handler`: Type \
+         `cling::extractors::State<handler_tests::Database>` was not collected"
+    ));
+}
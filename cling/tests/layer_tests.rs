@@ -0,0 +1,209 @@
+use std::sync::{Arc, Mutex};
+
+use cling::prelude::*;
+use cling::{BoxFuture, Invocation, InvocationKind, Layer, Next};
+
+#[derive(Run, Parser, Debug, Clone)]
+#[cling(run = "init")]
+struct App {
+    #[command(subcommand)]
+    cmd: Commands,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum Commands {
+    #[cling(run = "beep")]
+    Beep,
+    #[cling(run = "fail")]
+    Fail,
+}
+
+fn init() {}
+
+fn beep() {}
+
+fn fail() -> Result<(), CliError> {
+    Err(CliError::FailedWithMessage("failed".to_owned()))
+}
+
+/// Records every invocation it wraps along with its outcome.
+#[derive(Clone, Default)]
+struct Recorder {
+    name: &'static str,
+    events: Arc<Mutex<Vec<String>>>,
+}
+
+impl Layer for Recorder {
+    fn call<'a>(
+        self: Arc<Self>,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<(), CliError>> {
+        Box::pin(async move {
+            let invocation = next.invocation().clone();
+            let label = describe(&invocation);
+            self.push(format!("{}: enter {}", self.name, label));
+            let result = next.run().await;
+            self.push(format!(
+                "{}: exit {} ok={}",
+                self.name,
+                label,
+                result.is_ok()
+            ));
+            result
+        })
+    }
+}
+
+impl Recorder {
+    fn push(&self, event: String) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+fn describe(invocation: &Invocation) -> String {
+    match invocation.kind() {
+        | InvocationKind::Command => invocation.type_name().to_owned(),
+        | _ => invocation.handler_name().unwrap().to_owned(),
+    }
+}
+
+#[tokio::test]
+async fn layers_wrap_commands_and_handlers() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let outer = Recorder {
+        name: "outer",
+        events: events.clone(),
+    };
+    let inner = Recorder {
+        name: "inner",
+        events: events.clone(),
+    };
+
+    let finished = Cling::<App>::try_parse_from(["app", "beep"])
+        .unwrap()
+        .layer(outer)
+        .layer(inner)
+        .run()
+        .await;
+    assert!(finished.is_success());
+
+    let events = events.lock().unwrap().clone();
+    assert_eq!(
+        vec![
+            "outer: enter App",
+            "inner: enter App",
+            "outer: enter init",
+            "inner: enter init",
+            "inner: exit init ok=true",
+            "outer: exit init ok=true",
+            "outer: enter Commands",
+            "inner: enter Commands",
+            "outer: enter beep",
+            "inner: enter beep",
+            "inner: exit beep ok=true",
+            "outer: exit beep ok=true",
+            "inner: exit Commands ok=true",
+            "outer: exit Commands ok=true",
+            "inner: exit App ok=true",
+            "outer: exit App ok=true",
+        ],
+        events
+    );
+}
+
+#[tokio::test]
+async fn layers_observe_failures() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorder = Recorder {
+        name: "rec",
+        events: events.clone(),
+    };
+
+    let finished = Cling::<App>::try_parse_from(["app", "fail"])
+        .unwrap()
+        .layer(recorder)
+        .run()
+        .await;
+    assert!(finished.is_failure());
+
+    let events = events.lock().unwrap().clone();
+    assert!(events.contains(&"rec: exit fail ok=false".to_owned()));
+    assert_eq!("rec: exit App ok=false", events.last().unwrap());
+}
+
+async fn deny_fail(next: Next<'_>) -> Result<(), CliError> {
+    if next.invocation().handler_name() == Some("fail") {
+        // Short-circuit without running the handler.
+        return Err(CliError::FailedWithMessageAndCode(
+            "not allowed".to_owned(),
+            77,
+        ));
+    }
+    next.run().await
+}
+
+async fn skip_everything(_next: Next<'_>) -> Result<(), CliError> {
+    Ok(())
+}
+
+#[tokio::test]
+async fn layers_can_short_circuit() {
+    let finished = Cling::<App>::try_parse_from(["app", "fail"])
+        .unwrap()
+        .layer(cling::from_fn(deny_fail))
+        .run()
+        .await;
+    assert_eq!(77, finished.result().unwrap_err().exit_code());
+
+    let finished = Cling::<App>::try_parse_from(["app", "fail"])
+        .unwrap()
+        .layer(cling::from_fn(skip_everything))
+        .run()
+        .await;
+    assert!(finished.is_success());
+}
+
+/// Records the level of every invocation.
+#[derive(Default)]
+struct LevelRecorder(Mutex<Vec<String>>);
+
+impl Layer for LevelRecorder {
+    fn call<'a>(
+        self: Arc<Self>,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<(), CliError>> {
+        let invocation = next.invocation();
+        self.0.lock().unwrap().push(format!(
+            "{} {}",
+            describe(invocation),
+            invocation.level()
+        ));
+        next.run()
+    }
+}
+
+#[tokio::test]
+async fn invocations_report_their_level() {
+    let recorder = Arc::new(LevelRecorder::default());
+    let finished = Cling::<App>::try_parse_from(["app", "beep"])
+        .unwrap()
+        .layer(SharedLayer(recorder.clone()))
+        .run()
+        .await;
+    assert!(finished.is_success());
+    assert_eq!(
+        vec!["App 0", "init 0", "Commands 0", "beep 1"],
+        *recorder.0.lock().unwrap()
+    );
+}
+
+struct SharedLayer<L>(Arc<L>);
+
+impl<L: Layer> Layer for SharedLayer<L> {
+    fn call<'a>(
+        self: Arc<Self>,
+        next: Next<'a>,
+    ) -> BoxFuture<'a, Result<(), CliError>> {
+        self.0.clone().call(next)
+    }
+}
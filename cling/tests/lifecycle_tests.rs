@@ -0,0 +1,220 @@
+use std::sync::{Arc, Mutex};
+
+use cling::prelude::*;
+use cling::{InvocationKind, Next};
+
+#[derive(Clone, Default)]
+struct Journal(Arc<Mutex<Vec<String>>>);
+
+impl Journal {
+    fn push(&self, event: impl Into<String>) {
+        self.0.lock().unwrap().push(event.into());
+    }
+
+    fn events(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[derive(Run, Collect, Parser, Debug, Clone)]
+#[cling(run = "init", finally = "cleanup")]
+struct App {
+    #[arg(long)]
+    fail_init: bool,
+    #[command(subcommand)]
+    cmd: Commands,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum Commands {
+    #[cling(run = "ok")]
+    Ok,
+    #[cling(run = "fail")]
+    Fail,
+    Nested(Nested),
+}
+
+#[derive(Run, Collect, Args, Debug, Clone)]
+#[cling(run = "nested", finally = "nested_cleanup")]
+struct Nested {
+    #[arg(long)]
+    fail_cleanup: bool,
+}
+
+fn init(State(journal): State<Journal>, app: &App) -> Result<(), CliError> {
+    journal.push("init");
+    if app.fail_init {
+        return Err(CliError::FailedWithMessage("init failed".to_owned()));
+    }
+    Ok(())
+}
+
+fn ok(State(journal): State<Journal>) {
+    journal.push("ok");
+}
+
+fn fail(State(journal): State<Journal>) -> Result<(), CliError> {
+    journal.push("fail");
+    Err(CliError::FailedWithMessageAndCode("fail".to_owned(), 3))
+}
+
+fn nested(State(journal): State<Journal>) {
+    journal.push("nested");
+}
+
+fn nested_cleanup(
+    State(journal): State<Journal>,
+    nested: &Nested,
+    outcome: &Result<(), CliError>,
+) -> Result<(), CliError> {
+    journal.push(format!("nested_cleanup ok={}", outcome.is_ok()));
+    if nested.fail_cleanup {
+        return Err(CliError::FailedWithMessageAndCode(
+            "cleanup failed".to_owned(),
+            4,
+        ));
+    }
+    Ok(())
+}
+
+fn cleanup(State(journal): State<Journal>, outcome: &Result<(), CliError>) {
+    journal.push(format!("cleanup ok={}", outcome.is_ok()));
+}
+
+async fn run(args: &[&str]) -> (Result<(), CliError>, Vec<String>) {
+    let journal = Journal::default();
+    let finished = Cling::<App>::try_parse_from(args)
+        .unwrap()
+        .run_with_state(journal.clone())
+        .await;
+    (finished.result(), journal.events())
+}
+
+#[tokio::test]
+async fn finally_runs_on_success() {
+    let (result, events) = run(&["app", "ok"]).await;
+    assert!(result.is_ok());
+    assert_eq!(vec!["init", "ok", "cleanup ok=true"], events);
+}
+
+#[tokio::test]
+async fn finally_runs_when_subcommand_fails() {
+    let (result, events) = run(&["app", "fail"]).await;
+    assert_eq!(3, result.unwrap_err().exit_code());
+    assert_eq!(vec!["init", "fail", "cleanup ok=false"], events);
+}
+
+#[tokio::test]
+async fn finally_runs_when_own_handler_fails() {
+    let (result, events) = run(&["app", "--fail-init", "ok"]).await;
+    assert!(result.is_err());
+    assert_eq!(vec!["init", "cleanup ok=false"], events);
+}
+
+#[tokio::test]
+async fn finally_runs_at_every_level() {
+    let (result, events) = run(&["app", "nested"]).await;
+    assert!(result.is_ok());
+    assert_eq!(
+        vec![
+            "init",
+            "nested",
+            "nested_cleanup ok=true",
+            "cleanup ok=true"
+        ],
+        events
+    );
+
+    // A failing finally handler fails the command, and the outer finally
+    // handler observes the failure.
+    let (result, events) = run(&["app", "nested", "--fail-cleanup"]).await;
+    assert_eq!(4, result.unwrap_err().exit_code());
+    assert_eq!(
+        vec![
+            "init",
+            "nested",
+            "nested_cleanup ok=true",
+            "cleanup ok=false"
+        ],
+        events
+    );
+}
+
+#[derive(Run, Parser, Debug, Clone)]
+#[cling(run = "begin", after = "commit", finally = "close")]
+struct TxnApp {
+    #[command(subcommand)]
+    cmd: TxnCommands,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum TxnCommands {
+    #[cling(run = "write")]
+    Write,
+    #[cling(run = "fail")]
+    Fail,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Changes(u32);
+
+fn begin(State(journal): State<Journal>) {
+    journal.push("begin");
+}
+
+fn write(State(journal): State<Journal>) -> State<Changes> {
+    journal.push("write");
+    State(Changes(2))
+}
+
+fn commit(State(journal): State<Journal>, State(changes): State<Changes>) {
+    journal.push(format!("commit {}", changes.0));
+}
+
+fn close(State(journal): State<Journal>) {
+    journal.push("close");
+}
+
+async fn run_txn(args: &[&str]) -> (Result<(), CliError>, Vec<String>) {
+    let journal = Journal::default();
+    let finished = Cling::<TxnApp>::try_parse_from(args)
+        .unwrap()
+        .run_with_state(journal.clone())
+        .await;
+    (finished.result(), journal.events())
+}
+
+#[tokio::test]
+async fn after_runs_once_subcommands_succeed() {
+    let (result, events) = run_txn(&["app", "write"]).await;
+    assert!(result.is_ok());
+    assert_eq!(vec!["begin", "write", "commit 2", "close"], events);
+}
+
+#[tokio::test]
+async fn after_is_skipped_when_subcommands_fail() {
+    let (result, events) = run_txn(&["app", "fail"]).await;
+    assert!(result.is_err());
+    assert_eq!(vec!["begin", "fail", "close"], events);
+}
+
+async fn clear_before_finally(mut next: Next<'_>) -> Result<(), CliError> {
+    if next.invocation().kind() == InvocationKind::Finally {
+        next.args_mut().clear();
+    }
+    next.run().await
+}
+
+#[tokio::test]
+async fn finally_survives_layers_clearing_args() {
+    let journal = Journal::default();
+    let finished = Cling::<App>::try_parse_from(["app", "fail"])
+        .unwrap()
+        .layer(cling::from_fn(clear_before_finally))
+        .run_with_state(journal.clone())
+        .await;
+    // The cleanup handler can't extract its parameters anymore, but the
+    // command's own error is still reported.
+    assert_eq!(3, finished.result().unwrap_err().exit_code());
+    assert_eq!(vec!["init", "fail"], journal.events());
+}
@@ -0,0 +1,47 @@
+use cling::prelude::*;
+use cling::testing::TestRunner;
+use cling::VerbosityArgs;
+
+#[derive(Run, Parser, Debug, Clone)]
+#[cling(run = "init")]
+#[command(name = "app")]
+struct App {
+    #[command(flatten)]
+    verbosity: VerbosityArgs,
+    #[command(subcommand)]
+    cmd: Commands,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum Commands {
+    #[cling(run = "list")]
+    List,
+}
+
+fn init() {
+    tracing::info!("connecting");
+}
+
+fn list() {
+    tracing::info!("listing");
+    tracing::debug!("listing in detail");
+}
+
+// The subscriber is global and installed once per process, so this test has
+// its own test binary (see Cargo.toml) and must stay the only test in it.
+#[tokio::test]
+async fn verbosity_flags_install_a_subscriber() {
+    let outcome = TestRunner::<App>::new().run(["app", "list", "-vvv"]).await;
+    assert!(outcome.is_success());
+    let lines: Vec<&str> = outcome.stderr.lines().collect();
+    assert_eq!(3, lines.len(), "{}", outcome.stderr);
+    // The subscriber is installed once the root command collected the flags,
+    // so the root handler already logs at the requested level.
+    assert!(lines[0].starts_with(" INFO handler{handler=\"init\""));
+    assert!(lines[0].ends_with("connecting"));
+    // Subcommands run within the span of their parent command.
+    assert!(lines[1].starts_with(" INFO command{type_name=\"Commands\""));
+    assert!(lines[1].ends_with("listing"));
+    assert!(lines[2].starts_with("DEBUG"));
+    assert!(lines[2].ends_with("listing in detail"));
+}
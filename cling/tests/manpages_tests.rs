@@ -0,0 +1,66 @@
+#![cfg(feature = "manpages")]
+use cling::prelude::*;
+
+#[derive(Run, Parser, Debug, Clone)]
+#[command(name = "app", about = "Manages projects")]
+struct App {
+    #[command(subcommand)]
+    cmd: Commands,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum Commands {
+    /// Manage projects
+    #[command(subcommand)]
+    Projects(ProjectCommands),
+    #[command(hide = true)]
+    #[cling(run = "noop")]
+    Secret,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum ProjectCommands {
+    /// List all projects
+    #[cling(run = "noop")]
+    List,
+    /// Create a project
+    Create(Create),
+}
+
+#[derive(Run, Collect, Args, Debug, Clone)]
+#[cling(run = "noop")]
+struct Create {
+    /// Name of the project
+    #[arg(long)]
+    name: String,
+}
+
+fn noop() {}
+
+#[test]
+fn renders_one_page_per_command() {
+    let dir = std::env::temp_dir()
+        .join(format!("cling-manpages-{}", std::process::id()));
+    let pages = Cling::<App>::render_manpages(&dir).unwrap();
+
+    let mut names: Vec<_> = pages
+        .iter()
+        .map(|page| page.file_name().unwrap().to_string_lossy().into_owned())
+        .collect();
+    names.sort();
+    assert_eq!(
+        vec![
+            "app-projects-create.1",
+            "app-projects-list.1",
+            "app-projects.1",
+            "app.1",
+        ],
+        names
+    );
+
+    let create =
+        std::fs::read_to_string(dir.join("app-projects-create.1")).unwrap();
+    assert!(create.contains("Name of the project"));
+    assert!(create.contains("app projects create"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,143 @@
+#![cfg(feature = "markdown")]
+use cling::prelude::*;
+use cling::InvocationKind;
+
+/// Manages projects
+#[derive(Run, Collect, Parser, Debug, Clone)]
+#[command(name = "app")]
+#[cling(run = "init", finally = "cleanup")]
+struct App {
+    #[command(flatten)]
+    common: CommonOpts,
+    #[command(subcommand)]
+    cmd: Commands,
+}
+
+#[derive(Collect, Args, Debug, Clone)]
+struct CommonOpts {
+    /// Server to connect to
+    #[arg(long, env = "APP_SERVER", default_value = "localhost")]
+    server: String,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum Commands {
+    /// Manage projects
+    #[command(subcommand)]
+    Projects(ProjectCommands),
+    /// Print the version
+    #[command(name = "ver")]
+    #[cling(run = "version")]
+    Version,
+    #[command(hide = true)]
+    #[cling(run = "version")]
+    Secret,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+#[command(rename_all = "snake_case")]
+enum ProjectCommands {
+    /// Create a project
+    Create(Create),
+    /// List all projects
+    #[cling(run = "version")]
+    ListAll,
+}
+
+#[derive(Run, Collect, Args, Debug, Clone)]
+#[cling(run = "create")]
+struct Create {
+    /// Name of the project
+    name: String,
+    #[arg(skip)]
+    #[cling(collect)]
+    tags: Vec<String>,
+}
+
+#[derive(Clone)]
+struct Db;
+
+fn init(_common: &CommonOpts) -> State<Db> {
+    State(Db)
+}
+
+fn cleanup(_outcome: &Result<(), CliError>) {}
+
+fn version() {}
+
+fn create(State(_db): State<Db>, _app: &App, _create: &Create) {}
+
+#[test]
+fn metadata_describes_handlers_and_collected_types() {
+    let metadata = Cling::<App>::metadata();
+    assert_eq!("App", metadata.type_name);
+    let handlers: Vec<_> = metadata
+        .handlers
+        .iter()
+        .map(|handler| handler.name)
+        .collect();
+    assert_eq!(vec!["init", "cleanup"], handlers);
+
+    let create = metadata
+        .find_subcommand("projects")
+        .and_then(|projects| projects.find_subcommand("create"))
+        .unwrap();
+    assert_eq!(InvocationKind::Run, create.handlers[0].kind);
+    assert_eq!(3, create.handlers[0].params.len());
+    assert!(create.handlers[0].params[0].contains("State<"));
+    assert_eq!(
+        "Commands::Version",
+        metadata.find_subcommand("ver").unwrap().type_name
+    );
+    // Hidden commands are described as well.
+    assert!(metadata.find_subcommand("secret").is_some());
+    // Names follow the `rename_all` case of the enum.
+    assert!(metadata
+        .find_subcommand("projects")
+        .and_then(|projects| projects.find_subcommand("list_all"))
+        .is_some());
+}
+
+#[test]
+fn renders_one_section_per_command() {
+    let reference = Cling::<App>::render_markdown();
+    let sections: Vec<_> = reference
+        .lines()
+        .filter(|line| line.starts_with("## "))
+        .collect();
+    assert_eq!(
+        vec![
+            "## `app`",
+            "## `app projects`",
+            "## `app projects create`",
+            "## `app projects list_all`",
+            "## `app ver`"
+        ],
+        sections
+    );
+
+    assert!(reference.contains(indoc::indoc! {"
+        **Handlers:**
+
+        - run: `init(&CommonOpts)`
+        - finally: `cleanup(&Result<(), CliError>)`
+
+        **Collects:** `App`, `CommonOpts`
+    "}));
+    assert!(reference.contains(
+        "| `--server <SERVER>` | Server to connect to | `localhost` | \
+         `APP_SERVER` |"
+    ));
+    assert!(reference.contains(indoc::indoc! {"
+        - [`projects`](#app-projects): Manage projects
+        - [`ver`](#app-ver): Print the version
+    "}));
+    assert!(reference.contains(indoc::indoc! {"
+        **Handlers:**
+
+        - run: `create(State<Db>, &App, &Create)`
+
+        **Collects:** `Create`, `Collected<Vec<String>>`
+    "}));
+    assert!(reference.contains("| `<NAME>` | Name of the project |  |  |"));
+}
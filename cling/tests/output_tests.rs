@@ -0,0 +1,204 @@
+#![cfg(feature = "output")]
+use cling::prelude::*;
+use cling::testing::TestRunner;
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::Serialize;
+
+#[derive(Run, Parser, Debug, Clone)]
+struct App {
+    #[command(flatten)]
+    output: OutputArgs,
+    #[command(subcommand)]
+    cmd: Commands,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum Commands {
+    #[cling(run = "list")]
+    List,
+    #[cling(run = "list_none")]
+    Empty,
+    #[cling(run = "fail")]
+    Fail,
+    #[cling(run = "stream_projects")]
+    Stream,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+struct Project {
+    name: String,
+    stars: u32,
+}
+
+fn projects() -> Vec<Project> {
+    vec![
+        Project {
+            name: "cling".to_owned(),
+            stars: 100,
+        },
+        Project {
+            name: "clap".to_owned(),
+            stars: 15000,
+        },
+    ]
+}
+
+fn list() -> Output<Vec<Project>> {
+    Output::new(projects())
+}
+
+fn list_none() -> Output<Vec<Project>> {
+    Output::new(Vec::new())
+}
+
+fn fail() -> Result<Output<Vec<Project>>, CliError> {
+    Err(CliError::FailedWithMessage("no projects".to_owned()))
+}
+
+fn stream_projects() -> impl Stream<Item = Project> + Send {
+    stream::iter(projects()).then(|project| {
+        async move {
+            tokio::task::yield_now().await;
+            project
+        }
+    })
+}
+
+#[test]
+fn renders_all_formats() {
+    let output = Output::new(projects());
+    assert_eq!(
+        "NAME   STARS\ncling  100\nclap   15000",
+        output.render(OutputFormat::Table).unwrap()
+    );
+    assert_eq!(
+        "name: cling\nstars: 100\n",
+        Output::new(&projects()[0])
+            .render(OutputFormat::Yaml)
+            .unwrap()
+    );
+    assert_eq!(
+        "{\n  \"name\": \"cling\",\n  \"stars\": 100\n}",
+        Output::new(&projects()[0])
+            .render(OutputFormat::Json)
+            .unwrap()
+    );
+    let output = output.with_text("2 projects");
+    assert_eq!("2 projects", output.render(OutputFormat::Text).unwrap());
+    // Custom text is only used for the text format.
+    assert!(output.render(OutputFormat::Json).unwrap().starts_with('['));
+}
+
+#[tokio::test]
+async fn output_flag_is_global() {
+    let finished = Cling::<App>::try_parse_from(["app", "list", "-o", "json"])
+        .unwrap()
+        .run()
+        .await;
+    assert!(finished.is_success());
+    assert_eq!(
+        Some(OutputFormat::Json),
+        finished
+            .collected_parameters()
+            .get::<OutputArgs>()
+            .map(|args| args.output)
+    );
+}
+
+#[tokio::test]
+async fn errors_are_not_rendered() {
+    let finished = Cling::<App>::try_parse_from(["app", "fail"])
+        .unwrap()
+        .run()
+        .await;
+    assert_eq!(
+        "Failed: no projects",
+        finished.result().unwrap_err().to_string()
+    );
+}
+
+#[derive(Parser, Debug)]
+struct Flags {
+    #[command(flatten)]
+    output: OutputArgs,
+}
+
+fn output_args(args: &[&str]) -> OutputArgs {
+    Flags::try_parse_from(std::iter::once("app").chain(args.iter().copied()))
+        .unwrap()
+        .output
+}
+
+#[test]
+fn templates_and_fields() {
+    let args =
+        output_args(&["--fields", "name", "--format", "{{.name}}:{{.stars}}"]);
+    assert_eq!(
+        "cling:\nclap:",
+        Output::new(projects()).render_with(&args).unwrap()
+    );
+    // Custom text is ignored once fields or a template are selected.
+    let output = Output::new(projects()).with_text("2 projects");
+    let args = output_args(&["--fields", "stars", "-o", "table"]);
+    assert_eq!("STARS\n100\n15000", output.render_with(&args).unwrap());
+    assert_eq!("2 projects", output.render_with(&output_args(&[])).unwrap());
+}
+
+#[test]
+fn invalid_templates_are_rejected() {
+    let err =
+        Cling::<App>::try_parse_from(["app", "list", "--format", "{{.name"])
+            .err()
+            .unwrap();
+    assert!(err
+        .to_string()
+        .contains("unterminated placeholder `{{.name`"));
+}
+
+#[tokio::test]
+async fn streams_are_rendered() {
+    for format in ["text", "json", "yaml", "table", "csv"] {
+        let finished =
+            Cling::<App>::try_parse_from(["app", "stream", "-o", format])
+                .unwrap()
+                .run()
+                .await;
+        assert!(finished.is_success());
+    }
+}
+
+#[tokio::test]
+async fn rendered_output_is_captured() {
+    let runner = TestRunner::<App>::new();
+    let outcome = runner.run(["app", "list", "-o", "csv"]).await;
+    assert_eq!("name,stars\ncling,100\nclap,15000\n", outcome.stdout);
+
+    let outcome = runner.run(["app", "stream", "-o", "json"]).await;
+    assert_eq!(
+        "{\"name\":\"cling\",\"stars\":100}\n{\"name\":\"clap\",\"stars\":\
+         15000}\n",
+        outcome.stdout
+    );
+
+    // An empty list prints nothing, not a blank line.
+    let outcome = runner.run(["app", "empty"]).await;
+    assert_eq!("", outcome.stdout);
+    let outcome = runner.run(["app", "empty", "-o", "json"]).await;
+    assert_eq!("[]\n", outcome.stdout);
+
+    let outcome = runner.run(["app", "list", "-o", "yaml"]).await;
+    assert_eq!(
+        "- name: cling\n  stars: 100\n- name: clap\n  stars: 15000\n",
+        outcome.stdout
+    );
+    let outcome = runner.run(["app", "stream", "-o", "yaml"]).await;
+    assert_eq!(
+        "- name: cling\n  stars: 100\n- name: clap\n  stars: 15000\n",
+        outcome.stdout
+    );
+
+    let outcome = runner.run(["app", "fail", "-o", "json"]).await;
+    assert_eq!(1, outcome.exit_code);
+    assert_eq!("", outcome.stdout);
+    assert_eq!("no projects\n", outcome.stderr);
+}
@@ -0,0 +1,111 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use cling::prelude::*;
+use cling::{from_fn, ExplainArgs, Next, PlanStep};
+
+static HANDLER_CALLS: AtomicUsize = AtomicUsize::new(0);
+static LAYER_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Run, Parser, Debug, Clone)]
+#[cling(run = "init", finally = "cleanup")]
+struct App {
+    #[command(flatten)]
+    explain: ExplainArgs,
+    #[command(flatten)]
+    common: CommonOpts,
+    #[command(subcommand)]
+    cmd: Commands,
+}
+
+#[derive(Args, Collect, Debug, Clone)]
+struct CommonOpts {
+    #[arg(long, global = true)]
+    verbose: bool,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum Commands {
+    Projects(Projects),
+}
+
+#[derive(Run, Args, Collect, Debug, Clone)]
+#[cling(run = "list", after = "summary")]
+struct Projects {
+    #[arg(long)]
+    filter: Option<String>,
+}
+
+fn handler() {
+    HANDLER_CALLS.fetch_add(1, Ordering::SeqCst);
+}
+
+fn init(_common: &CommonOpts) {
+    handler()
+}
+
+fn list(_projects: &Projects) {
+    handler()
+}
+
+fn summary() {
+    handler()
+}
+
+fn cleanup(_outcome: &Result<(), CliError>) {
+    handler()
+}
+
+async fn count_layer(next: Next<'_>) -> Result<(), CliError> {
+    LAYER_CALLS.fetch_add(1, Ordering::SeqCst);
+    next.run().await
+}
+
+fn describe(step: &PlanStep) -> (String, &'static str, usize) {
+    (step.kind.to_string(), step.handler, step.level)
+}
+
+#[tokio::test]
+async fn plan_lists_handlers_without_running_them() {
+    let plan = Cling::<App>::try_parse_from(["app", "projects"])
+        .unwrap()
+        .layer(from_fn(count_layer))
+        .plan()
+        .await
+        .unwrap();
+    assert_eq!(
+        vec![
+            ("run".to_owned(), "init", 0),
+            ("run".to_owned(), "list", 1),
+            ("after".to_owned(), "summary", 1),
+            ("finally".to_owned(), "cleanup", 0),
+        ],
+        plan.steps.iter().map(describe).collect::<Vec<_>>()
+    );
+    assert_eq!(vec!["&CommonOpts"], plan.steps[0].params);
+    assert_eq!(vec!["&Projects"], plan.steps[1].params);
+    assert!(plan.steps[2].params.is_empty());
+    assert_eq!(vec!["&Result<(), CliError>"], plan.steps[3].params);
+    assert_eq!(
+        indoc::indoc! {"
+            1. run `init(&CommonOpts)` of `App` (level 0)
+            2. run `list(&Projects)` of `Projects` (level 1)
+            3. after `summary()` of `Projects` (level 1)
+            4. finally `cleanup(&Result<(), CliError>)` of `App` (level 0)
+        "},
+        plan.to_string()
+    );
+
+    // --explain plans instead of running.
+    let finished =
+        Cling::<App>::try_parse_from(["app", "projects", "--explain"])
+            .unwrap()
+            .layer(from_fn(count_layer))
+            .run()
+            .await;
+    assert!(finished.is_success());
+
+    assert_eq!(0, HANDLER_CALLS.load(Ordering::SeqCst));
+    // `--explain` is collected while dispatching the top-level command, so
+    // only that dispatch went through the layer.
+    assert_eq!(1, LAYER_CALLS.load(Ordering::SeqCst));
+}
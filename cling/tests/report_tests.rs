@@ -0,0 +1,146 @@
+use std::time::Duration;
+
+use cling::prelude::*;
+use cling::testing::TestRunner;
+use cling::{
+    ExecutionReport,
+    HandlerOutcome,
+    InvocationKind,
+    Next,
+    TimingsArgs,
+};
+
+#[derive(Run, Parser, Debug, Clone)]
+#[cling(run = "connect", finally = "disconnect")]
+struct App {
+    #[command(flatten)]
+    timings: TimingsArgs,
+    #[command(subcommand)]
+    cmd: Commands,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum Commands {
+    #[cling(run = "list")]
+    List,
+    #[cling(run = "fail")]
+    Fail,
+}
+
+async fn connect() {
+    tokio::time::sleep(Duration::from_millis(20)).await;
+}
+
+fn list() {}
+
+fn fail() -> Result<(), CliError> {
+    Err(CliError::FailedWithMessageAndCode("no luck".to_owned(), 3))
+}
+
+fn disconnect() {}
+
+fn summary(report: &ExecutionReport) -> Vec<(InvocationKind, &str, usize)> {
+    report
+        .handlers
+        .iter()
+        .map(|run| (run.kind, run.handler, run.level))
+        .collect()
+}
+
+#[tokio::test]
+async fn reports_handlers_that_ran() {
+    let finished = Cling::<App>::try_parse_from(["app", "list"])
+        .unwrap()
+        .record_execution(true)
+        .run()
+        .await;
+    let report = finished.execution_report();
+    assert_eq!(
+        vec![
+            (InvocationKind::Run, "connect", 0),
+            (InvocationKind::Run, "list", 1),
+            (InvocationKind::Finally, "disconnect", 0),
+        ],
+        summary(&report)
+    );
+    assert!(report.handlers[0].duration >= Duration::from_millis(20));
+    assert!(report.total() >= report.handlers[0].duration);
+    assert!(report
+        .handlers
+        .iter()
+        .all(|run| run.outcome == HandlerOutcome::Succeeded));
+}
+
+#[tokio::test]
+async fn reports_failures() {
+    let finished = Cling::<App>::try_parse_from(["app", "fail"])
+        .unwrap()
+        .record_execution(true)
+        .run()
+        .await;
+    let report = finished.execution_report();
+    assert_eq!(
+        HandlerOutcome::Failed {
+            exit_code: 3,
+            message: "Error: no luck".to_owned(),
+        },
+        report.handlers[1].outcome
+    );
+    // The cleanup handler still runs, and succeeds.
+    assert_eq!("disconnect", report.handlers[2].handler);
+    #[cfg(feature = "output")]
+    assert!(serde_json::to_string(&report).unwrap().contains(
+        "\"handler\":\"fail\",\"type\":\"Commands::Fail\",\"level\":1,"
+    ));
+}
+
+async fn skip_list(next: Next<'_>) -> Result<(), CliError> {
+    if next.invocation().handler_name() == Some("list") {
+        return Ok(());
+    }
+    next.run().await
+}
+
+#[tokio::test]
+async fn skipped_handlers_are_not_reported() {
+    let finished = Cling::<App>::try_parse_from(["app", "list"])
+        .unwrap()
+        .record_execution(true)
+        .layer(cling::from_fn(skip_list))
+        .run()
+        .await;
+    let handlers: Vec<_> = finished
+        .execution_report()
+        .handlers
+        .iter()
+        .map(|run| run.handler)
+        .collect();
+    assert_eq!(vec!["connect", "disconnect"], handlers);
+}
+
+#[tokio::test]
+async fn handlers_are_not_recorded_by_default() {
+    let finished = Cling::<App>::try_parse_from(["app", "list"])
+        .unwrap()
+        .run()
+        .await;
+    assert!(finished.is_success());
+    assert_eq!(ExecutionReport::default(), finished.execution_report());
+}
+
+#[tokio::test]
+async fn timings_flag_prints_report() {
+    let runner = TestRunner::<App>::new();
+    let outcome = runner.run(["app", "list", "--timings"]).await;
+    assert!(outcome.is_success());
+    assert_eq!("", outcome.stdout);
+    let lines: Vec<&str> = outcome.stderr.lines().collect();
+    assert_eq!("Timings:", lines[0]);
+    assert!(lines[1].ends_with("ok      run `connect` of `App`"));
+    assert!(lines[2].ends_with("ok        run `list` of `Commands::List`"));
+    assert!(lines[3].ends_with("ok      finally `disconnect` of `App`"));
+    assert!(lines[4].ends_with("total"));
+
+    let outcome = runner.run(["app", "list"]).await;
+    assert_eq!("", outcome.stderr);
+}
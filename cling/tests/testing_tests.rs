@@ -0,0 +1,243 @@
+use std::io::{Read, Write};
+
+use cling::prelude::*;
+use cling::testing::{call_handler, Sandbox, TestRunner};
+use cling::{CollectedArgs, Next};
+
+#[derive(Run, Parser, Debug, Clone)]
+struct App {
+    #[command(flatten)]
+    dry_run: DryRunArgs,
+    #[command(subcommand)]
+    cmd: Commands,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum Commands {
+    Greet(GreetArgs),
+    #[cling(run = "fail")]
+    Fail,
+    #[cling(run = "delete")]
+    Delete,
+    Config(ConfigArgs),
+    #[cling(run = "echo")]
+    Echo,
+}
+
+#[derive(Run, Args, Collect, Debug, Clone)]
+#[cling(run = "config")]
+struct ConfigArgs {
+    #[arg(long, env = "TESTING_TESTS_PROFILE")]
+    profile: String,
+}
+
+#[derive(Run, Args, Collect, Debug, Clone)]
+#[cling(run = "greet")]
+struct GreetArgs {
+    name: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Greeting(String);
+
+async fn greet(
+    State(greeting): State<Greeting>,
+    args: &GreetArgs,
+) -> Result<State<usize>, CliError> {
+    writeln!(cling::stdout(), "{}, {}!", greeting.0, args.name)?;
+    writeln!(cling::stderr(), "greeted")?;
+    Ok(State(1))
+}
+
+fn fail() -> Result<(), CliError> {
+    Err(CliError::FailedWithMessageAndCode("no luck".to_owned(), 3))
+}
+
+fn delete(dry_run: DryRun) {
+    dry_run.mutation("Delete everything").run(|| ());
+}
+
+fn config(env: Environment, args: &ConfigArgs) -> Result<(), CliError> {
+    // The process environment is never changed.
+    assert_eq!(None, std::env::var_os("TESTING_TESTS_PROFILE"));
+    let config = std::fs::read_to_string(env.path("config.toml"))?;
+    let home = env.var("TESTING_TESTS_HOME").unwrap_or_default();
+    write!(cling::stdout(), "{}|{}|{}", args.profile, home, config)?;
+    Ok(())
+}
+
+fn echo() -> Result<(), CliError> {
+    let mut input = String::new();
+    cling::stdin().read_to_string(&mut input)?;
+    write!(cling::stdout(), "{}", input.to_uppercase())?;
+    Ok(())
+}
+
+fn runner() -> TestRunner<App> {
+    TestRunner::new().with_state(Greeting("Hello".to_owned()))
+}
+
+#[tokio::test]
+async fn captures_handler_output() {
+    let outcome = runner().run(["app", "greet", "Ferris"]).await;
+    assert!(outcome.is_success());
+    assert_eq!("Hello, Ferris!\n", outcome.stdout);
+    assert_eq!("greeted\n", outcome.stderr);
+    assert_eq!(Some(&State(1)), outcome.collected.get::<State<usize>>());
+    assert_eq!(
+        Some(&State(Greeting("Hello".to_owned()))),
+        outcome.collected.get::<State<Greeting>>()
+    );
+
+    // Every run starts from the injected states.
+    let outcome = runner().run(["app", "greet", "Ada"]).await;
+    assert_eq!("Hello, Ada!\n", outcome.stdout);
+}
+
+#[tokio::test]
+async fn captures_errors() {
+    let outcome = runner().run(["app", "fail"]).await;
+    assert_eq!(3, outcome.exit_code);
+    assert_eq!("", outcome.stdout);
+    assert_eq!("no luck\n", outcome.stderr);
+
+    let outcome = runner().run(["app", "greet"]).await;
+    assert_eq!(2, outcome.exit_code);
+    assert!(outcome.stderr.contains("<NAME>"));
+    assert!(outcome.collected.collected_types().is_empty());
+
+    // Help isn't an error, clap prints it to stdout.
+    let outcome = runner().run(["app", "--help"]).await;
+    assert!(outcome.is_success());
+    assert!(outcome.stdout.contains("Usage: app"));
+    assert_eq!("", outcome.stderr);
+}
+
+#[tokio::test]
+async fn captures_dry_run_plans() {
+    let outcome = runner().run(["app", "--dry-run", "delete"]).await;
+    assert!(outcome.is_success());
+    assert_eq!(
+        "Dry run: nothing was changed. Planned changes:\n  - Delete \
+         everything\n",
+        outcome.stdout
+    );
+}
+
+async fn skip_fail(next: Next<'_>) -> Result<(), CliError> {
+    if next.invocation().handler_name() == Some("fail") {
+        return Ok(());
+    }
+    next.run().await
+}
+
+#[tokio::test]
+async fn runs_layers() {
+    let runner = runner().layer(cling::from_fn(skip_fail));
+    let outcome = runner.run(["app", "fail"]).await;
+    assert!(outcome.is_success());
+}
+
+#[tokio::test]
+async fn sandbox_isolates_env_and_files() {
+    let sandbox = Sandbox::new()
+        .unwrap()
+        .env("TESTING_TESTS_PROFILE", "dev")
+        .env("TESTING_TESTS_HOME", "/home/ferris");
+    std::fs::write(sandbox.path().join("config.toml"), "debug = true").unwrap();
+    let cwd = std::env::current_dir().unwrap();
+
+    let sandboxed = runner().sandbox(sandbox.clone());
+    let outcome = sandboxed.run(["app", "config"]).await;
+    assert_eq!("dev|/home/ferris|debug = true", outcome.stdout);
+    let outcome = sandboxed.run(["app", "config", "--profile", "prod"]).await;
+    assert_eq!("prod|/home/ferris|debug = true", outcome.stdout);
+
+    // Nothing leaks out of the sandbox.
+    assert_eq!(None, std::env::var_os("TESTING_TESTS_PROFILE"));
+    assert_eq!(cwd, std::env::current_dir().unwrap());
+
+    // Without the variable, the required argument is missing.
+    let sandboxed =
+        runner().sandbox(sandbox.env_remove("TESTING_TESTS_PROFILE"));
+    let outcome = sandboxed.run(["app", "config"]).await;
+    assert_eq!(2, outcome.exit_code);
+    assert!(outcome.stderr.contains("--profile <PROFILE>"));
+}
+
+async fn yield_first(next: Next<'_>) -> Result<(), CliError> {
+    tokio::task::yield_now().await;
+    next.run().await
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn sandboxed_runs_interleave_on_one_thread() {
+    let sandboxed = |profile: &str| {
+        let sandbox = Sandbox::new()
+            .unwrap()
+            .env("TESTING_TESTS_PROFILE", profile);
+        std::fs::write(sandbox.path().join("config.toml"), profile).unwrap();
+        runner().layer(cling::from_fn(yield_first)).sandbox(sandbox)
+    };
+    let (dev, prod) = (sandboxed("dev"), sandboxed("prod"));
+    let (dev, prod) =
+        tokio::join!(dev.run(["app", "config"]), prod.run(["app", "config"]));
+    assert_eq!("dev||dev", dev.stdout);
+    assert_eq!("prod||prod", prod.stdout);
+}
+
+#[tokio::test]
+async fn sandbox_working_directory() {
+    let sandbox = Sandbox::new()
+        .unwrap()
+        .env("TESTING_TESTS_PROFILE", "dev")
+        .current_dir("nested");
+    let nested = sandbox.path().join("nested");
+    std::fs::create_dir(&nested).unwrap();
+    std::fs::write(nested.join("config.toml"), "nested").unwrap();
+
+    let outcome = runner().sandbox(sandbox).run(["app", "config"]).await;
+    assert_eq!("dev||nested", outcome.stdout);
+}
+
+#[tokio::test]
+async fn sandbox_provides_stdin() {
+    let sandbox = Sandbox::new().unwrap().stdin("hello");
+    let outcome = runner().sandbox(sandbox).run(["app", "echo"]).await;
+    assert_eq!("HELLO", outcome.stdout);
+
+    // Without a sandbox, there is no input rather than the test's own stdin.
+    let outcome = runner().run(["app", "echo"]).await;
+    assert!(outcome.is_success());
+    assert_eq!("", outcome.stdout);
+}
+
+#[test]
+fn sandbox_directory_is_removed() {
+    let sandbox = Sandbox::new().unwrap();
+    let path = sandbox.path().to_owned();
+    let clone = sandbox.clone();
+    drop(sandbox);
+    assert!(path.is_dir());
+    drop(clone);
+    assert!(!path.exists());
+}
+
+#[tokio::test]
+async fn handlers_can_be_called_directly() {
+    let mut args = CollectedArgs::builder()
+        .with(&GreetArgs {
+            name: "Ferris".to_owned(),
+        })
+        .with_state(Greeting("Hi".to_owned()))
+        .build();
+    call_handler!(greet, &mut args).await.unwrap();
+    assert_eq!(Some(&State(1)), args.get::<State<usize>>());
+
+    let err = call_handler!(fail, &mut args).await.unwrap_err();
+    assert_eq!(3, err.exit_code());
+
+    // `ConfigArgs` was never collected.
+    let err = call_handler!(config, &mut args).await.unwrap_err();
+    assert!(matches!(err, CliError::InvalidHandler(_)));
+}
@@ -0,0 +1,102 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use cling::prelude::*;
+use cling::{BoxFuture, ThreadTimer, Timer};
+
+#[derive(Run, Parser, Debug, Clone)]
+#[cling(run = "init", timeout = "1s")]
+struct App {
+    #[command(subcommand)]
+    cmd: Commands,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum Commands {
+    #[cling(run = "quick", timeout = "5s")]
+    Quick,
+    #[cling(run = "slow", timeout = "50ms")]
+    Slow,
+    Nested(Nested),
+}
+
+#[derive(Run, Args, Debug, Clone)]
+#[cling(run = "slow", timeout = "1m30s")]
+struct Nested {}
+
+async fn init() {}
+
+async fn quick() -> State<u32> {
+    State(1)
+}
+
+async fn slow() {
+    std::future::pending::<()>().await
+}
+
+#[tokio::test]
+async fn handlers_within_timeout_succeed() {
+    let finished = Cling::<App>::try_parse_from(["app", "quick"])
+        .unwrap()
+        .run()
+        .await;
+    assert!(finished.is_success());
+    // Effects of handlers with a timeout are applied.
+    assert_eq!(
+        Some(&State(1)),
+        finished.collected_parameters().get::<State<u32>>()
+    );
+}
+
+#[tokio::test]
+async fn handlers_exceeding_timeout_fail() {
+    let finished = Cling::<App>::try_parse_from(["app", "slow"])
+        .unwrap()
+        .run()
+        .await;
+    let error = finished.result().unwrap_err();
+    let CliError::TimedOut(handler, timeout) = &error else {
+        panic!("expected a timeout, got {:?}", error);
+    };
+    assert_eq!("slow", handler);
+    assert_eq!(Duration::from_millis(50), *timeout);
+    assert_eq!(124, error.exit_code());
+}
+
+/// Completes every sleep immediately and counts them.
+#[derive(Clone, Default)]
+struct InstantTimer(Arc<AtomicUsize>);
+
+impl Timer for InstantTimer {
+    fn sleep(&self, _duration: Duration) -> BoxFuture<'static, ()> {
+        self.0.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async {})
+    }
+}
+
+#[tokio::test]
+async fn custom_timers_are_used() {
+    let timer = InstantTimer::default();
+    let finished = Cling::<App>::try_parse_from(["app", "nested"])
+        .unwrap()
+        .timer(timer.clone())
+        .run()
+        .await;
+    // `init` completes on its first poll, before the timer is polled.
+    assert_eq!(2, timer.0.load(Ordering::SeqCst));
+    assert!(matches!(
+        finished.result().unwrap_err(),
+        CliError::TimedOut(_, timeout) if timeout == Duration::from_secs(90)
+    ));
+}
+
+#[tokio::test]
+async fn thread_timer_wakes_the_earliest_deadline_first() {
+    let long = ThreadTimer.sleep(Duration::from_secs(60));
+    let short = ThreadTimer.sleep(Duration::from_millis(10));
+    tokio::select! {
+        _ = long => panic!("the long sleep completed first"),
+        _ = short => {}
+    }
+}
@@ -0,0 +1,118 @@
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use cling::prelude::*;
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::Subscriber;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+#[derive(Run, Parser, Debug, Clone)]
+#[cling(run = "init")]
+#[command(name = "app")]
+struct App {
+    #[command(subcommand)]
+    cmd: Commands,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum Commands {
+    #[cling(run = "list")]
+    List,
+    Project(ProjectArgs),
+}
+
+#[derive(Run, Args, Debug, Clone)]
+#[cling(run = "show_project")]
+struct ProjectArgs {
+    #[command(subcommand)]
+    cmd: ProjectCommands,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+enum ProjectCommands {
+    #[cling(run = "create")]
+    #[command(name = "new")]
+    Create,
+}
+
+fn init() {}
+fn list() {}
+fn show_project() {}
+fn create() {}
+
+/// Records every span as `parent > name {fields}`.
+#[derive(Clone, Default)]
+struct SpanRecorder(Arc<Mutex<Vec<String>>>);
+
+struct Fields(Vec<String>);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0.push(format!("{}={:?}", field.name(), value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.push(format!("{}={}", field.name(), value));
+    }
+}
+
+impl<S> Layer<S> for SpanRecorder
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        attrs: &Attributes<'_>,
+        id: &Id,
+        ctx: Context<'_, S>,
+    ) {
+        let mut fields = Fields(Vec::new());
+        attrs.record(&mut fields);
+        let parent = ctx
+            .span(id)
+            .and_then(|span| span.parent())
+            .map(|parent| parent.name())
+            .unwrap_or("-");
+        self.0.lock().unwrap().push(format!(
+            "{} > {} {{{}}}",
+            parent,
+            attrs.metadata().name(),
+            fields.0.join(" ")
+        ));
+    }
+}
+
+async fn record_spans(args: &[&str]) -> Vec<String> {
+    let recorder = SpanRecorder::default();
+    let subscriber = tracing_subscriber::registry().with(recorder.clone());
+    let _guard = tracing::subscriber::set_default(subscriber);
+    let finished = Cling::<App>::try_parse_from(args).unwrap().run().await;
+    assert!(finished.is_success());
+    let spans = recorder.0.lock().unwrap().clone();
+    spans
+}
+
+#[tokio::test]
+async fn spans_follow_the_command_tree() {
+    assert_eq!(
+        vec![
+            "- > command {type_name=App command=app level=0}",
+            "command > handler {handler=init kind=run type_name=App \
+             command=app level=0}",
+            "command > command {type_name=Commands command=app level=0}",
+            "command > handler {handler=list kind=run \
+             type_name=Commands::List command=app list level=1}",
+        ],
+        record_spans(&["app", "list"]).await
+    );
+
+    let spans = record_spans(&["app", "project", "new"]).await;
+    assert_eq!(
+        "command > handler {handler=create kind=run \
+         type_name=ProjectCommands::Create command=app project new level=2}",
+        spans.last().unwrap()
+    );
+}
@@ -2,8 +2,6 @@ use std::io::Write;
 
 use cling::prelude::*;
 use colored::Colorize;
-use rustyline::error::ReadlineError;
-use rustyline::DefaultEditor;
 
 #[derive(Run, Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
@@ -23,10 +21,47 @@ pub enum Commands {
     AskQuestion,
     // Guess a number between 1 and 10
     Guess(Guess),
+    /// Count how many times this command has been called
+    #[cling(run = "count")]
+    Count,
     /// Who am I?
     #[command(name = "whoami")]
     #[cling(run = "whoami")]
     WhoAmI,
+    /// Simple arithmetic. Type `calc` to enter its scope.
+    Calc(Calc),
+}
+
+#[derive(Run, Args, Collect, Debug, Clone)]
+#[cling(run = "calc")]
+pub struct Calc {
+    /// Round results to this many decimal places
+    #[arg(long, default_value_t = 2)]
+    pub precision: usize,
+    #[clap(subcommand)]
+    pub command: CalcCommands,
+}
+
+#[derive(Run, Subcommand, Debug, Clone)]
+pub enum CalcCommands {
+    /// Add two numbers
+    Add(Add),
+    /// Divide two numbers
+    Divide(Divide),
+}
+
+#[derive(Run, Collect, Args, Debug, Clone)]
+#[cling(run = "add")]
+pub struct Add {
+    pub a: f64,
+    pub b: f64,
+}
+
+#[derive(Run, Collect, Args, Debug, Clone)]
+#[cling(run = "divide")]
+pub struct Divide {
+    pub a: f64,
+    pub b: f64,
 }
 
 #[derive(Collect, Args, Debug, Clone)]
@@ -42,6 +77,9 @@ pub struct Guess {
     pub num: u8,
 }
 
+#[derive(Clone, Debug)]
+pub struct Counter(u32);
+
 // Handlers
 fn ask() {
     let mut name = String::new();
@@ -62,6 +100,30 @@ fn whoami() {
     println!("I'm groot!");
 }
 
+// State returned by handlers is kept across lines in the REPL.
+fn count(State(counter): State<Counter>) -> State<Counter> {
+    let counter = Counter(counter.0 + 1);
+    println!("Called {} time(s)", counter.0);
+    State(counter)
+}
+
+// Runs once when entering the `calc` scope in the REPL.
+fn calc(calc: &Calc) {
+    println!("Calculator ready (precision: {})", calc.precision);
+}
+
+fn add(calc: &Calc, Add { a, b }: &Add) {
+    println!("{:.*}", calc.precision, a + b);
+}
+
+fn divide(calc: &Calc, Divide { a, b }: &Divide) -> Result<(), CliError> {
+    if *b == 0.0 {
+        return Err(CliError::FailedWithMessage("division by zero".to_owned()));
+    }
+    println!("{:.*}", calc.precision, a / b);
+    Ok(())
+}
+
 pub fn guess(guess: &Guess) -> Result<(), CliError> {
     let random = rand::random::<u8>() % 10;
     if guess.num == random {
@@ -79,39 +141,6 @@ pub fn guess(guess: &Guess) -> Result<(), CliError> {
 
 #[tokio::main]
 async fn main() -> ClingFinished<CliOpts> {
-    // Setting up a simple REPL loop.
-    println!("Welcome to the sample REPL!");
-    let mut rl = DefaultEditor::new().unwrap();
-
-    loop {
-        let readline = rl.readline(">> ");
-        match readline {
-            | Ok(line) => {
-                let opts = Cling::<CliOpts>::try_parse_str(&line);
-                match opts {
-                    | Ok(opts) => {
-                        let output = opts.run().await;
-                        if let Err(e) = output.result() {
-                            e.print().unwrap();
-                        }
-                    }
-                    | Err(e) => e.print().unwrap(),
-                };
-            }
-            | Err(ReadlineError::Interrupted) => {
-                // Program terminates with exit code 1
-                return Cling::failed(CliError::FailedWithMessage(
-                    "CTRL-C".to_owned(),
-                ));
-            }
-            | Err(ReadlineError::Eof) => {
-                // Program terminates with exit code 0
-                println!("CTRL-D");
-                return Cling::success();
-            }
-            | Err(err) => {
-                return Cling::failed(CliError::Other(err.into()));
-            }
-        }
-    }
+    println!("Welcome to the sample REPL! Type `help` to list commands.");
+    Cling::<CliOpts>::repl().with_state(Counter(0)).run().await
 }